@@ -1,20 +1,416 @@
-use crate::game::{Game, Tile};
+use crate::clock::now;
+use crate::game::{Game, MoveFuture, Outcome, PieceType, Player, Tile};
+use crate::mcts::{self, Mcts};
+use crate::nn::{Network, PolicyValueNet};
+use crate::rng::Rng;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
 
+#[derive(Clone)]
 pub enum AIKind {
     Minimax(u32),
+    /// uniformly random legal moves
+    Random {
+        seed: u64,
+    },
+    /// best `Game::score` after one move
+    Greedy,
+    /// wins if it can, otherwise captures as many pieces as possible
+    CaptureSeeker,
+    /// PUCT tree search, guided by the network if there is one
+    Mcts {
+        simulations: u32,
+        network: Option<Arc<PolicyValueNet>>,
+    },
 }
 
+/// How positions are scored at the leaves of a search
+#[derive(Clone, Default)]
+pub enum Evaluator {
+    /// hand tuned `Game::score`
+    #[default]
+    Heuristic,
+    /// trained value network, see `nn.rs`
+    Network(Arc<Network>),
+}
+
+impl Evaluator {
+    /// same scale as `Game::score`, positive favours the defenders
+    pub fn score(&self, game: &Game) -> i32 {
+        match self {
+            Evaluator::Heuristic => game.score(),
+            // finished games keep their win and loss scores
+            Evaluator::Network(_) if game.game_over => game.score(),
+            Evaluator::Network(network) => (network.evaluate(game) * 1000.0) as i32,
+        }
+    }
+
+    /// Which way the position leans, from -1 when the attackers have won to 1
+    /// when the defenders have, for showing on an evaluation bar
+    pub fn balance(&self, game: &Game) -> f32 {
+        match game.outcome() {
+            Some(Outcome::DefenderWin) => return 1.0,
+            Some(Outcome::AttackerWin) => return -1.0,
+            Some(Outcome::Draw) => return 0.0,
+            None => (),
+        }
+        match self {
+            Evaluator::Heuristic => self.score_balance(game.score()),
+            Evaluator::Network(network) => network.evaluate(game).clamp(-1.0, 1.0),
+        }
+    }
+
+    /// `balance` for a `score`, such as one found by a search
+    pub fn score_balance(&self, score: i32) -> f32 {
+        match self {
+            // the heuristic counts material, so even is the starting position's score
+            Evaluator::Heuristic => {
+                let even = Game::new().score();
+                (score.saturating_sub(even) as f32 / 150.0).tanh()
+            }
+            Evaluator::Network(_) => (score as f32 / 1000.0).clamp(-1.0, 1.0),
+        }
+    }
+}
+
+/// Named strength presets, see `AIPlayer::with_difficulty`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+    ];
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Difficulty::Beginner => "beginner",
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.to_string() == s)
+            .ok_or_else(|| format!("unknown difficulty {s}"))
+    }
+}
+
+/// simulations for `mcts` specs that don't give a number
+pub const DEFAULT_SIMULATIONS: u32 = 400;
+
+#[derive(Clone)]
 pub struct AIPlayer {
     pub kind: AIKind,
+    pub evaluator: Evaluator,
+    /// mate finder run before the normal search, off unless set
+    pub proof_search: Option<ProofSearch>,
+    /// stop searching once this much time has passed, for every kind of search
+    pub time_limit: Option<Duration>,
+    /// random amount up to this size added to or removed from each move's score
+    pub eval_noise: i32,
+    /// pick at random between moves scoring within this much of the best
+    pub selection_margin: i32,
+    pub rng: Rng,
+    /// file the network was loaded from, so the player can still be described by its spec
+    pub network_path: Option<String>,
+    /// the position the last search moved to and how it saw it, see `balance`
+    pub last_search: RefCell<Option<(Game, f32)>>,
 }
 
 impl AIPlayer {
+    pub fn new(kind: AIKind) -> Self {
+        let rng = match &kind {
+            AIKind::Random { seed } => Rng::new(*seed),
+            _ => Rng::from_entropy(),
+        };
+        AIPlayer {
+            kind,
+            evaluator: Evaluator::default(),
+            proof_search: None,
+            time_limit: None,
+            eval_noise: 0,
+            selection_margin: 0,
+            rng,
+            network_path: None,
+            last_search: RefCell::new(None),
+        }
+    }
+
+    /// Builds a player from a short text description, as used on the command line
+    ///
+    /// `minimax:DEPTH[:VALUE_NETWORK]`, `random[:SEED]`, `greedy`, `capture`,
+    /// `mcts[:SIMULATIONS[:POLICY_NETWORK]]` or a difficulty such as `hard`,
+    /// followed by any of `:time=SECONDS`, `:noise=N`, `:margin=N` and `:proof`
+    /// to set the time limit, evaluation noise, selection margin and mate finder
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let (settings, parts) = spec
+            .split(':')
+            .partition::<Vec<&str>, _>(|part| part.contains('=') || *part == "proof");
+        let mut parts = parts.into_iter();
+        let name = parts.next().unwrap_or_default();
+        let number = |part: Option<&str>| {
+            part.ok_or_else(|| format!("{name} needs a number"))?
+                .parse::<u64>()
+                .map_err(|_| format!("bad number in {spec}"))
+        };
+        let mut player = match name {
+            "minimax" => {
+                let depth = number(parts.next())? as u32;
+                let path = parts.next();
+                let evaluator = match path {
+                    Some(path) => Evaluator::Network(Arc::new(
+                        Network::load(path).map_err(|error| format!("{path}: {error}"))?,
+                    )),
+                    None => Evaluator::Heuristic,
+                };
+                AIPlayer {
+                    evaluator,
+                    network_path: path.map(str::to_string),
+                    ..AIPlayer::new(AIKind::Minimax(depth))
+                }
+            }
+            "random" => {
+                let seed = match parts.next() {
+                    Some(seed) => number(Some(seed))?,
+                    None => Rng::from_entropy().next_u64(),
+                };
+                AIPlayer::new(AIKind::Random { seed })
+            }
+            "greedy" => AIPlayer::new(AIKind::Greedy),
+            "capture" => AIPlayer::new(AIKind::CaptureSeeker),
+            "mcts" => {
+                let simulations = match parts.next() {
+                    Some(simulations) => number(Some(simulations))? as u32,
+                    None => DEFAULT_SIMULATIONS,
+                };
+                // without a simulation the root isn't expanded and there is no move
+                if simulations == 0 {
+                    return Err(format!("{spec} needs at least one simulation"));
+                }
+                let path = parts.next();
+                let network = match path {
+                    Some(path) => Some(Arc::new(
+                        PolicyValueNet::load(path).map_err(|error| format!("{path}: {error}"))?,
+                    )),
+                    None => None,
+                };
+                AIPlayer {
+                    network_path: path.map(str::to_string),
+                    ..AIPlayer::new(AIKind::Mcts {
+                        simulations,
+                        network,
+                    })
+                }
+            }
+            _ => AIPlayer::with_difficulty(name.parse()?),
+        };
+        if parts.next().is_some() {
+            return Err(format!("too many parts in {spec}"));
+        }
+        for setting in settings {
+            let (key, value) = setting.split_once('=').unwrap_or((setting, ""));
+            let bad_setting = || format!("bad setting {setting} in {spec}");
+            match key {
+                "time" => {
+                    let seconds = value.parse().map_err(|_| bad_setting())?;
+                    let limit = Duration::try_from_secs_f64(seconds).map_err(|_| bad_setting())?;
+                    player.time_limit = Some(limit);
+                }
+                "noise" => player.eval_noise = value.parse().map_err(|_| bad_setting())?,
+                "margin" => player.selection_margin = value.parse().map_err(|_| bad_setting())?,
+                "proof" if value.is_empty() => player.proof_search = Some(ProofSearch::default()),
+                _ => return Err(bad_setting()),
+            }
+        }
+        Ok(player)
+    }
+
+    /// The spec `from_spec` would rebuild this player from as it is now,
+    /// without difficulty names. None if it can't be described, such as a
+    /// network that wasn't loaded from a file or a custom mate finder.
+    /// Random players are described without their seed, as their numbers
+    /// move on with every move.
+    fn full_spec(&self) -> Option<String> {
+        let mut spec = match &self.kind {
+            AIKind::Minimax(depth) => format!("minimax:{depth}"),
+            AIKind::Random { .. } => "random".to_string(),
+            AIKind::Greedy => "greedy".to_string(),
+            AIKind::CaptureSeeker => "capture".to_string(),
+            AIKind::Mcts { simulations, .. } => format!("mcts:{simulations}"),
+        };
+        let network = match &self.kind {
+            AIKind::Minimax(_) => matches!(self.evaluator, Evaluator::Network(_)),
+            AIKind::Mcts { network, .. } => network.is_some(),
+            // other players have nowhere to give a network in their spec
+            _ if matches!(self.evaluator, Evaluator::Network(_)) => return None,
+            _ => false,
+        };
+        if network {
+            spec.push(':');
+            spec.push_str(self.network_path.as_deref()?);
+        }
+        if let Some(limit) = self.time_limit {
+            spec.push_str(&format!(":time={}", limit.as_secs_f64()));
+        }
+        if self.eval_noise != 0 {
+            spec.push_str(&format!(":noise={}", self.eval_noise));
+        }
+        if self.selection_margin != 0 {
+            spec.push_str(&format!(":margin={}", self.selection_margin));
+        }
+        match self.proof_search {
+            None => (),
+            Some(search) if search == ProofSearch::default() => spec.push_str(":proof"),
+            Some(_) => return None,
+        }
+        Some(spec)
+    }
+
+    /// Preset strengths, weaker levels search less deeply and play
+    /// deliberately imperfect moves so they can be beaten
+    pub fn with_difficulty(difficulty: Difficulty) -> Self {
+        // a defender is worth 40 and the king 400 in Game::score
+        let (depth, proof_search, time_limit, eval_noise, selection_margin) = match difficulty {
+            Difficulty::Beginner => (1, false, None, 150, 100),
+            Difficulty::Easy => (2, false, None, 60, 40),
+            Difficulty::Medium => (3, true, None, 20, 10),
+            Difficulty::Hard => (4, true, None, 0, 2),
+            Difficulty::Expert => (6, true, Some(Duration::from_secs(5)), 0, 0),
+        };
+        AIPlayer {
+            proof_search: proof_search.then(ProofSearch::default),
+            time_limit,
+            eval_noise,
+            selection_margin,
+            ..AIPlayer::new(AIKind::Minimax(depth))
+        }
+    }
+
+    /// How this player sees `game`, from -1 for the attackers winning to 1 for
+    /// the defenders. What its last search found if `game` is the position that
+    /// search moved to, otherwise its evaluation or the network guiding its search.
+    pub fn balance(&self, game: &Game) -> f32 {
+        if let Some((searched, balance)) = &*self.last_search.borrow() {
+            if searched.board == game.board && searched.defenders_turn == game.defenders_turn {
+                return *balance;
+            }
+        }
+        match &self.kind {
+            AIKind::Mcts {
+                network: Some(network),
+                ..
+            } if !game.game_over => network.evaluate(game, &[]).1,
+            _ => self.evaluator.balance(game),
+        }
+    }
+
     pub fn take_turn(&self, game: &Game) -> (Tile, Tile) {
-        match self.kind {
-            AIKind::Minimax(depth) => self.minimax_turn_rayon(game, depth),
+        let ((src, dest), balance) = self.search(game);
+        *self.last_search.borrow_mut() = balance.map(|balance| (game.gen_next(src, dest), balance));
+        (src, dest)
+    }
+
+    /// The move to play, with how the position after it looks to the
+    /// search for the kinds of player that score positions
+    fn search(&self, game: &Game) -> ((Tile, Tile), Option<f32>) {
+        let deadline = Deadline::after(self.time_limit);
+        // play a proven forced win if one is found within budget,
+        // leaving most of the time for the normal search
+        if let Some(search) = &self.proof_search {
+            let proof_deadline = Deadline::after(self.time_limit.map(|limit| limit / 4));
+            if let ProofResult::Proven(line) = search.solve_until(game, proof_deadline) {
+                let won = if game.defenders_turn { 1.0 } else { -1.0 };
+                return (line[0], Some(won));
+            }
+        }
+        match &self.kind {
+            AIKind::Minimax(depth) => {
+                let (mv, score) = self.minimax_turn_rayon(game, *depth, deadline);
+                (mv, Some(self.evaluator.score_balance(score)))
+            }
+            AIKind::Random { .. } => (self.random_turn(game), None),
+            AIKind::Greedy => {
+                let (mv, score) = self.greedy_turn(game);
+                (mv, Some(self.evaluator.score_balance(score)))
+            }
+            AIKind::CaptureSeeker => (self.capture_seeker_turn(game), None),
+            AIKind::Mcts {
+                simulations,
+                network,
+            } => {
+                let mut search = Mcts::new(game, network.as_deref());
+                let visits = search.search_while(*simulations, None, || !deadline.expired());
+                let mv = mcts::best_move(&visits);
+                (mv, search.defender_value(mv))
+            }
         }
     }
+
+    fn random_turn(&self, game: &Game) -> (Tile, Tile) {
+        let moves = game.get_all_valid_moves().collect::<Vec<(Tile, Tile)>>();
+        moves[self.rng.below(moves.len())]
+    }
+
+    fn greedy_turn(&self, game: &Game) -> ((Tile, Tile), i32) {
+        let scored = game
+            .get_all_valid_moves()
+            .map(|(src, dest)| {
+                let score = self.evaluator.score(&game.gen_next(src, dest));
+                ((src, dest), score)
+            })
+            .collect();
+        self.select_move(game, scored)
+    }
+
+    fn capture_seeker_turn(&self, game: &Game) -> (Tile, Tile) {
+        let pieces = piece_count(game);
+        let scored = game
+            .get_all_valid_moves()
+            .map(|(src, dest)| {
+                let next = game.gen_next(src, dest);
+                // any win beats any number of captures
+                let priority = if next.game_over {
+                    i32::MAX
+                } else {
+                    (pieces - piece_count(&next)) as i32
+                };
+                // select_move expects scores from the defenders view
+                let score = if game.defenders_turn {
+                    priority
+                } else {
+                    -priority
+                };
+                ((src, dest), score)
+            })
+            .collect();
+        // priorities aren't scores of the position
+        self.select_move(game, scored).0
+    }
     // minimax ai
     // legacy function to use / reference if you dont want rayon
     fn _minimax_turn(&self, game: &Game, depth: u32) -> (Tile, Tile) {
@@ -24,15 +420,18 @@ impl AIPlayer {
         let mut best_src = None;
         let mut best_dest = None;
 
-        let mut best_score = if is_maximizing {
-            std::i32::MIN
-        } else {
-            std::i32::MAX
-        };
+        let mut best_score = if is_maximizing { i32::MIN } else { i32::MAX };
 
         for (src, dest) in game.get_all_valid_moves() {
             let new_game = game.gen_next(src, dest);
-            let score = minimax(new_game, depth, std::i32::MIN, std::i32::MAX);
+            let score = minimax(
+                new_game,
+                depth,
+                i32::MIN,
+                i32::MAX,
+                Deadline::none(),
+                &self.evaluator,
+            );
             if best_dest.is_none() {
                 // init to first move
                 best_src = Some(src);
@@ -53,37 +452,147 @@ impl AIPlayer {
         (best_src.unwrap(), best_dest.unwrap())
     }
 
-    fn minimax_turn_rayon(&self, game: &Game, depth: u32) -> (Tile, Tile) {
-        let moves = game.get_all_valid_moves().collect::<Vec<(Tile, Tile)>>();
-        let a = moves.par_iter().map(|(src, dest)| {
-            let new_game = game.gen_next(*src, *dest);
-            let score = minimax(new_game, depth, std::i32::MIN, std::i32::MAX);
-            (src, dest, score)
+    fn minimax_turn_rayon(
+        &self,
+        game: &Game,
+        depth: u32,
+        deadline: Deadline,
+    ) -> ((Tile, Tile), i32) {
+        if deadline.0.is_none() {
+            let scored = score_moves(game, depth, deadline, &self.evaluator);
+            return self.select_move(game, scored);
+        }
+
+        // iterative deepening, keeping the last search that finished in time
+        let mut scored = score_moves(game, 0, Deadline::none(), &self.evaluator);
+        for depth in 1..=depth {
+            let deeper = score_moves(game, depth, deadline, &self.evaluator);
+            if deadline.expired() {
+                break;
+            }
+            scored = deeper;
+        }
+        self.select_move(game, scored)
+    }
+
+    /// Picks among the best scoring moves, applying any noise and margin.
+    /// Returns the move with its score as it was given.
+    fn select_move(&self, game: &Game, scored: Vec<((Tile, Tile), i32)>) -> ((Tile, Tile), i32) {
+        // score from the view of the player moving, higher is better
+        let sign = if game.defenders_turn { 1 } else { -1 };
+        let noisy = scored
+            .iter()
+            .map(|(_, score)| {
+                score
+                    .saturating_mul(sign)
+                    .saturating_add(self.rng.noise(self.eval_noise))
+            })
+            .collect::<Vec<i32>>();
+        let best = noisy.iter().copied().max().expect("no valid moves");
+        let candidates = scored
+            .into_iter()
+            .zip(noisy)
+            .filter(|(_, noisy)| *noisy >= best.saturating_sub(self.selection_margin))
+            .map(|(scored, _)| scored)
+            .collect::<Vec<((Tile, Tile), i32)>>();
+        candidates[self.rng.below(candidates.len())]
+    }
+}
+
+fn piece_count(game: &Game) -> usize {
+    game.board
+        .0
+        .iter()
+        .flatten()
+        .filter(|&&piece| piece != PieceType::Blank)
+        .count()
+}
+
+impl Player for AIPlayer {
+    fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a> {
+        Box::pin(std::future::ready(Some(self.take_turn(game).into())))
+    }
+
+    // replaces any fixed time limit, the clock is what matters in a timed game
+    fn time_budget(&mut self, budget: Duration) {
+        self.time_limit = Some(budget);
+    }
+
+    fn evaluate(&self, game: &Game) -> Option<f32> {
+        Some(self.balance(game))
+    }
+
+    // built from the current settings so changes like a new time limit are kept
+    fn spec(&self) -> Option<String> {
+        let spec = self.full_spec()?;
+        let preset = Difficulty::ALL.into_iter().find(|&difficulty| {
+            AIPlayer::with_difficulty(difficulty).full_spec() == Some(spec.clone())
         });
+        Some(preset.map_or(spec, |difficulty| difficulty.to_string()))
+    }
+}
 
-        let (best_src, best_dest, _best_score) = if game.defenders_turn {
-            a.max_by_key(|(_, _, score)| *score).unwrap()
-        } else {
-            a.min_by_key(|(_, _, score)| *score).unwrap()
-        };
+/// minimax score of every move, evaluated in parallel with the parallel feature
+fn score_moves(
+    game: &Game,
+    depth: u32,
+    deadline: Deadline,
+    evaluator: &Evaluator,
+) -> Vec<((Tile, Tile), i32)> {
+    let moves = game.get_all_valid_moves().collect::<Vec<(Tile, Tile)>>();
+    #[cfg(feature = "parallel")]
+    let moves = moves.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let moves = moves.iter();
+    moves
+        .map(|&(src, dest)| {
+            let new_game = game.gen_next(src, dest);
+            let score = minimax(new_game, depth, i32::MIN, i32::MAX, deadline, evaluator);
+            ((src, dest), score)
+        })
+        .collect()
+}
+
+/// Point in time a search should give up at, in seconds from `now`
+#[derive(Clone, Copy)]
+struct Deadline(Option<f64>);
+
+impl Deadline {
+    fn none() -> Self {
+        Deadline(None)
+    }
 
-        (*best_src, *best_dest)
+    fn after(limit: Option<Duration>) -> Self {
+        Deadline(limit.map(|limit| now() + limit.as_secs_f64()))
+    }
+
+    fn expired(&self) -> bool {
+        self.0.is_some_and(|end| now() >= end)
     }
 }
 
 // depth counts down and stops at zero
-fn minimax(game: Game, depth: u32, mut alpha: i32, mut beta: i32) -> i32 {
+// results are meaningless once the deadline has expired
+fn minimax(
+    game: Game,
+    depth: u32,
+    mut alpha: i32,
+    mut beta: i32,
+    deadline: Deadline,
+    evaluator: &Evaluator,
+) -> i32 {
     let discount = 0.99;
-    if depth == 0 || game.game_over {
-        game.score()
+    if depth == 0 || game.game_over || deadline.expired() {
+        evaluator.score(&game)
     } else if game.defenders_turn {
         //maximizing player
-        let mut max = std::i32::MIN;
+        let mut max = i32::MIN;
         for (src, dest) in game.get_all_valid_moves() {
             let new_game = game.gen_next(src, dest);
             max = std::cmp::max(
                 max,
-                (minimax(new_game, depth - 1, alpha, beta) as f32 * discount) as i32,
+                (minimax(new_game, depth - 1, alpha, beta, deadline, evaluator) as f32 * discount)
+                    as i32,
             );
             if max > beta {
                 break;
@@ -93,12 +602,13 @@ fn minimax(game: Game, depth: u32, mut alpha: i32, mut beta: i32) -> i32 {
         max
     } else {
         // minimizing agent
-        let mut min = std::i32::MAX;
+        let mut min = i32::MAX;
         for (src, dest) in game.get_all_valid_moves() {
             let new_game = game.gen_next(src, dest);
             min = std::cmp::min(
                 min,
-                (minimax(new_game, depth - 1, alpha, beta) as f32 * discount) as i32,
+                (minimax(new_game, depth - 1, alpha, beta, deadline, evaluator) as f32 * discount)
+                    as i32,
             );
             if min < alpha {
                 break;
@@ -109,10 +619,231 @@ fn minimax(game: Game, depth: u32, mut alpha: i32, mut beta: i32) -> i32 {
     }
 }
 
+//
+// Proof-number search
+//
+
+const PN_INF: u32 = u32::MAX;
+
+/// Result of a proof-number search, always from the view of the side to move
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProofResult {
+    /// forced win, with the principal line starting with the winning side's move
+    Proven(Vec<(Tile, Tile)>),
+    /// no forced win exists within max_depth plies
+    Disproven,
+    /// node budget ran out before the root was solved
+    Unknown,
+}
+
+/// Proof-number solver for forced wins of the side to move.
+/// Lines longer than max_depth plies count as not winning,
+/// and the search gives up after creating node_budget nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofSearch {
+    pub node_budget: usize,
+    pub max_depth: u32,
+}
+
+impl Default for ProofSearch {
+    fn default() -> Self {
+        // cheap enough to run before every turn
+        ProofSearch {
+            node_budget: 20_000,
+            max_depth: 5,
+        }
+    }
+}
+
+struct PnNode {
+    game: Game,
+    mv: Option<(Tile, Tile)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    depth: u32,
+    pn: u32,
+    dn: u32,
+}
+
+impl PnNode {
+    /// OR nodes are where the side we are proving a win for moves
+    fn is_or(&self) -> bool {
+        self.depth.is_multiple_of(2)
+    }
+}
+
+impl ProofSearch {
+    pub fn solve(&self, game: &Game) -> ProofResult {
+        self.solve_until(game, Deadline::none())
+    }
+
+    /// as `solve`, also giving up once the deadline passes
+    fn solve_until(&self, game: &Game, deadline: Deadline) -> ProofResult {
+        let mut tree = PnTree {
+            nodes: Vec::new(),
+            defender: game.defenders_turn,
+            max_depth: self.max_depth,
+        };
+        tree.add_node(game.clone(), None, None);
+
+        while !tree.solved(0) && tree.nodes.len() < self.node_budget && !deadline.expired() {
+            let leaf = tree.most_proving();
+            tree.expand(leaf);
+            tree.update_ancestors(leaf);
+        }
+
+        if tree.nodes[0].pn == 0 {
+            ProofResult::Proven(tree.principal_line())
+        } else if tree.nodes[0].dn == 0 {
+            ProofResult::Disproven
+        } else {
+            ProofResult::Unknown
+        }
+    }
+}
+
+struct PnTree {
+    nodes: Vec<PnNode>,
+    /// side the win is being proven for
+    defender: bool,
+    max_depth: u32,
+}
+
+impl PnTree {
+    fn add_node(&mut self, game: Game, mv: Option<(Tile, Tile)>, parent: Option<usize>) -> usize {
+        let depth = parent.map_or(0, |p| self.nodes[p].depth + 1);
+        let (pn, dn) = if game.game_over {
+            if game.defender_won == self.defender {
+                (0, PN_INF)
+            } else {
+                (PN_INF, 0)
+            }
+        } else if depth >= self.max_depth {
+            // out of plies, counts as not winning
+            (PN_INF, 0)
+        } else {
+            (1, 1)
+        };
+        self.nodes.push(PnNode {
+            game,
+            mv,
+            parent,
+            children: Vec::new(),
+            depth,
+            pn,
+            dn,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn solved(&self, node: usize) -> bool {
+        self.nodes[node].pn == 0 || self.nodes[node].dn == 0
+    }
+
+    fn most_proving(&self) -> usize {
+        let mut node = 0;
+        while !self.nodes[node].children.is_empty() {
+            let current = &self.nodes[node];
+            node = if current.is_or() {
+                *current
+                    .children
+                    .iter()
+                    .find(|&&child| self.nodes[child].pn == current.pn)
+                    .unwrap()
+            } else {
+                *current
+                    .children
+                    .iter()
+                    .find(|&&child| self.nodes[child].dn == current.dn)
+                    .unwrap()
+            };
+        }
+        node
+    }
+
+    fn expand(&mut self, node: usize) {
+        let game = self.nodes[node].game.clone();
+        let moves = game.get_all_valid_moves().collect::<Vec<(Tile, Tile)>>();
+        if moves.is_empty() {
+            // side to move is stuck and loses
+            let current = &mut self.nodes[node];
+            (current.pn, current.dn) = if current.is_or() {
+                (PN_INF, 0)
+            } else {
+                (0, PN_INF)
+            };
+            return;
+        }
+        for (src, dest) in moves {
+            let child = self.add_node(game.gen_next(src, dest), Some((src, dest)), Some(node));
+            self.nodes[node].children.push(child);
+        }
+    }
+
+    fn set_numbers(&mut self, node: usize) {
+        let children = &self.nodes[node].children;
+        if children.is_empty() {
+            return;
+        }
+        let pns = children.iter().map(|&child| self.nodes[child].pn);
+        let dns = children.iter().map(|&child| self.nodes[child].dn);
+        let (pn, dn) = if self.nodes[node].is_or() {
+            (pns.min().unwrap(), dns.fold(0, u32::saturating_add))
+        } else {
+            (pns.fold(0, u32::saturating_add), dns.min().unwrap())
+        };
+        self.nodes[node].pn = pn;
+        self.nodes[node].dn = dn;
+    }
+
+    fn update_ancestors(&mut self, node: usize) {
+        let mut current = Some(node);
+        while let Some(node) = current {
+            self.set_numbers(node);
+            current = self.nodes[node].parent;
+        }
+    }
+
+    /// plies until the win against the best defence, for proven nodes
+    fn proof_length(&self, node: usize) -> u32 {
+        let current = &self.nodes[node];
+        let proven_children = current
+            .children
+            .iter()
+            .filter(|&&child| self.nodes[child].pn == 0)
+            .map(|&child| self.proof_length(child) + 1);
+        if current.children.is_empty() {
+            0
+        } else if current.is_or() {
+            proven_children.min().unwrap()
+        } else {
+            proven_children.max().unwrap()
+        }
+    }
+
+    /// quickest win for the attacker of the proof, longest resistance for the defence
+    fn principal_line(&self) -> Vec<(Tile, Tile)> {
+        let mut line = Vec::new();
+        let mut node = 0;
+        while !self.nodes[node].children.is_empty() {
+            let children = self.nodes[node].children.iter().copied();
+            let proven = children.filter(|&child| self.nodes[child].pn == 0);
+            node = if self.nodes[node].is_or() {
+                proven.min_by_key(|&child| self.proof_length(child))
+            } else {
+                proven.max_by_key(|&child| self.proof_length(child))
+            }
+            .unwrap();
+            line.push(self.nodes[node].mv.unwrap());
+        }
+        line
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::game::{Board, PieceType};
+    use crate::game::{Board, Move};
 
     fn new_game(board: Board) -> Game {
         Game {
@@ -124,8 +855,11 @@ mod test {
     }
 
     fn take_minimax_turn(mut game: Game, depth: u32, defenders_turn: bool) -> Game {
+        // seeded so ties between moves are broken the same way every run
         let ai = AIPlayer {
-            kind: AIKind::Minimax(depth),
+            proof_search: None,
+            rng: Rng::new(1),
+            ..AIPlayer::new(AIKind::Minimax(depth))
         };
         game.defenders_turn = defenders_turn;
         let (src, dest) = ai.take_turn(&game);
@@ -189,7 +923,7 @@ mod test {
             println!("testing depth {i}");
             let game = run_minimax_game(game.clone(), 2, 3);
             assert!(game.game_over);
-            assert_eq!(game.defender_won, true);
+            assert!(game.defender_won);
         }
     }
 
@@ -208,7 +942,276 @@ mod test {
             let game = take_minimax_turn(game.clone(), i, false);
             println!("{}", game.board);
             assert!(game.game_over);
-            assert_eq!(game.defender_won, false);
+            assert!(!game.defender_won);
         }
     }
+
+    #[test]
+    fn proof_search_finds_escape() {
+        let mut board = Board::empty();
+        board.0[3][3] = PieceType::King;
+        board.0[2][2] = PieceType::Attacker;
+        let game = new_game(board);
+
+        let search = ProofSearch {
+            node_budget: 100_000,
+            max_depth: 3,
+        };
+        let ProofResult::Proven(line) = search.solve(&game) else {
+            panic!("king should have a forced escape");
+        };
+        // king steps off the throne, attacker moves, king escapes
+        assert_eq!(line.len(), 3);
+        let mut game = game;
+        for (src, dest) in line {
+            game = game.gen_next(src, dest);
+        }
+        assert!(game.game_over);
+        assert!(game.defender_won);
+    }
+
+    #[test]
+    fn proof_search_disproves_and_gives_up() {
+        let mut board = Board::empty();
+        board.0[3][3] = PieceType::King;
+        board.0[0][1] = PieceType::Attacker;
+        let mut game = new_game(board);
+        game.defenders_turn = false;
+
+        // a lone attacker cannot capture the king on the throne
+        let search = ProofSearch {
+            node_budget: 100_000,
+            max_depth: 3,
+        };
+        assert_eq!(search.solve(&game), ProofResult::Disproven);
+
+        let search = ProofSearch {
+            node_budget: 10,
+            max_depth: 9,
+        };
+        assert_eq!(search.solve(&Game::new()), ProofResult::Unknown);
+    }
+
+    #[test]
+    fn difficulty_presets_take_the_win() {
+        let mut board = Board::empty();
+        board.0[1][1] = PieceType::King;
+        board.0[0][1] = PieceType::Attacker;
+        board.0[2][5] = PieceType::Attacker;
+        let mut game = new_game(board);
+        game.defenders_turn = false;
+
+        // capturing the king is worth far more than any noise
+        for difficulty in Difficulty::ALL {
+            let ai = AIPlayer::with_difficulty(difficulty);
+            let (src, dest) = ai.take_turn(&game);
+            let next = game.gen_next(src, dest);
+            assert!(next.game_over, "{difficulty} missed the win");
+            assert!(!next.defender_won);
+        }
+    }
+
+    #[test]
+    fn near_best_moves_are_varied() {
+        let ai = AIPlayer {
+            rng: Rng::new(7),
+            ..AIPlayer::with_difficulty(Difficulty::Beginner)
+        };
+        let game = Game::new();
+        let first = ai.take_turn(&game);
+        assert!((0..20).any(|_| ai.take_turn(&game) != first));
+    }
+
+    /// plays at most max_turns, returning the final game
+    fn play_out(defender: &AIPlayer, attacker: &AIPlayer, max_turns: usize) -> Game {
+        let mut game = Game::new();
+        for _ in 0..max_turns {
+            if game.game_over {
+                break;
+            }
+            let player = if game.defenders_turn {
+                defender
+            } else {
+                attacker
+            };
+            let (src, dest) = player.take_turn(&game);
+            game = game.gen_next(src, dest);
+        }
+        game
+    }
+
+    #[test]
+    fn random_player_is_reproducible() {
+        let moves = |seed| {
+            let ai = AIPlayer::new(AIKind::Random { seed });
+            (0..10)
+                .map(|_| ai.take_turn(&Game::new()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(moves(3), moves(3));
+        assert_ne!(moves(3), moves(4));
+    }
+
+    #[test]
+    fn capture_seeker_takes_captures() {
+        let mut board = Board::empty();
+        board.0[3][3] = PieceType::King;
+        board.0[0][4] = PieceType::Attacker;
+        board.0[1][5] = PieceType::Defender;
+        board.0[1][6] = PieceType::Attacker;
+        let mut game = new_game(board);
+        game.defenders_turn = false;
+
+        let ai = AIPlayer::new(AIKind::CaptureSeeker);
+        let mv = Move::from(ai.take_turn(&game));
+        assert_eq!(mv.to_string(), "a3-b3");
+    }
+
+    #[test]
+    fn baselines_lose_to_minimax() {
+        // the mate finder sees the escapes a shallow search misses, and both
+        // sides are seeded so ties between moves are broken the same way every run
+        let minimax = AIPlayer {
+            proof_search: Some(ProofSearch::default()),
+            rng: Rng::new(1),
+            ..AIPlayer::new(AIKind::Minimax(1))
+        };
+        for seed in 0..3 {
+            let random = AIPlayer::new(AIKind::Random { seed });
+            let game = play_out(&minimax, &random, 200);
+            assert!(game.game_over && game.defender_won);
+        }
+        let greedy = AIPlayer {
+            rng: Rng::new(1),
+            ..AIPlayer::new(AIKind::Greedy)
+        };
+        let game = play_out(&minimax, &greedy, 200);
+        assert!(game.game_over && game.defender_won);
+    }
+
+    #[test]
+    fn network_evaluator_still_wins() {
+        let mut board = Board::empty();
+        board.0[1][1] = PieceType::King;
+        board.0[0][1] = PieceType::Attacker;
+        board.0[2][5] = PieceType::Attacker;
+        let mut game = new_game(board);
+        game.defenders_turn = false;
+
+        // an untrained network still scores the king capture as a win
+        let ai = AIPlayer {
+            evaluator: Evaluator::Network(Arc::new(Network::new(8, 0))),
+            proof_search: None,
+            ..AIPlayer::new(AIKind::Minimax(2))
+        };
+        let (src, dest) = ai.take_turn(&game);
+        assert!(game.gen_next(src, dest).game_over);
+    }
+
+    #[test]
+    fn players_from_spec() {
+        assert!(matches!(
+            AIPlayer::from_spec("minimax:3").unwrap().kind,
+            AIKind::Minimax(3)
+        ));
+        assert!(matches!(
+            AIPlayer::from_spec("random:9").unwrap().kind,
+            AIKind::Random { seed: 9 }
+        ));
+        assert!(matches!(
+            AIPlayer::from_spec("mcts:50").unwrap().kind,
+            AIKind::Mcts {
+                simulations: 50,
+                network: None
+            }
+        ));
+        assert!(AIPlayer::from_spec("expert").unwrap().time_limit.is_some());
+        let spec = |spec| AIPlayer::from_spec(spec).unwrap().spec();
+        assert_eq!(spec("easy").as_deref(), Some("easy"));
+        assert_eq!(spec("random:9").as_deref(), Some("random"));
+        assert_eq!(spec("minimax:4:margin=2:proof").as_deref(), Some("hard"));
+        // settings changed after building are described too
+        let mut hard = AIPlayer::with_difficulty(Difficulty::Hard);
+        hard.time_limit = Some(Duration::from_millis(2500));
+        let described = hard.spec().unwrap();
+        assert_eq!(described, "minimax:4:time=2.5:margin=2:proof");
+        assert_eq!(spec(&described).as_deref(), Some(described.as_str()));
+        // a network from memory has no file to name
+        let mcts = AIPlayer::new(AIKind::Mcts {
+            simulations: 8,
+            network: Some(Arc::new(PolicyValueNet::new(8, 0))),
+        });
+        assert_eq!(mcts.spec(), None);
+        assert!(AIPlayer::from_spec("hard:speed=2").is_err());
+        assert!(AIPlayer::from_spec("minimax").is_err());
+        assert!(AIPlayer::from_spec("greedy:2").is_err());
+        assert!(AIPlayer::from_spec("mcts:0").is_err());
+        assert!(AIPlayer::from_spec("grandmaster").is_err());
+    }
+    #[test]
+    fn searches_stop_at_the_time_limit() {
+        let limit = Duration::from_millis(50);
+        let ai = AIPlayer {
+            time_limit: Some(limit),
+            ..AIPlayer::new(AIKind::Mcts {
+                simulations: u32::MAX,
+                network: None,
+            })
+        };
+        let start = now();
+        ai.take_turn(&Game::new());
+        assert!(now() - start < 5.0);
+
+        let search = ProofSearch {
+            node_budget: usize::MAX,
+            max_depth: 20,
+        };
+        let start = now();
+        search.solve_until(&Game::new(), Deadline::after(Some(limit)));
+        assert!(now() - start < 5.0);
+    }
+
+    #[test]
+    fn evaluation_balance() {
+        let evaluator = Evaluator::Heuristic;
+        assert_eq!(evaluator.balance(&Game::new()), 0.0);
+        // an attacker down leans to the defenders
+        let mut game = Game::new();
+        game.board.0[3][0] = PieceType::Blank;
+        let balance = evaluator.balance(&game);
+        assert!(balance > 0.0 && balance < 1.0);
+
+        game.game_over = true;
+        assert_eq!(evaluator.balance(&game), -1.0);
+
+        // players judge by their own network
+        let network = Arc::new(PolicyValueNet::new(8, 0));
+        let ai = AIPlayer::new(AIKind::Mcts {
+            simulations: 1,
+            network: Some(Arc::clone(&network)),
+        });
+        let game = Game::new();
+        assert_eq!(ai.evaluate(&game), Some(network.evaluate(&game, &[]).1));
+        let ai = AIPlayer {
+            evaluator: Evaluator::Network(Arc::new(Network::new(8, 0))),
+            ..AIPlayer::new(AIKind::Minimax(1))
+        };
+        assert_eq!(ai.evaluate(&game), Some(ai.evaluator.balance(&game)));
+    }
+
+    #[test]
+    fn evaluation_follows_the_search() {
+        // the king has a forced escape that only a search sees
+        let mut board = Board::empty();
+        board.0[3][3] = PieceType::King;
+        board.0[2][2] = PieceType::Attacker;
+        let game = new_game(board);
+        let ai = AIPlayer::new(AIKind::Minimax(3));
+        let (src, dest) = ai.take_turn(&game);
+        let after = game.gen_next(src, dest);
+        assert!(ai.evaluator.balance(&after) < 1.0);
+        assert_eq!(ai.evaluate(&after), Some(1.0));
+        // other positions are still judged by the evaluator
+        assert_eq!(ai.evaluate(&game), Some(ai.evaluator.balance(&game)));
+    }
 }
@@ -0,0 +1,207 @@
+//! Reinforcement learning loop for the policy-value network
+//!
+//! Each iteration plays self-play games with the current network, trains on them,
+//! and writes a checkpoint. Every few iterations the network plays `Minimax(4)`.
+//!
+//! cargo run --release --bin selfplay -- --iterations 50 --checkpoint-dir checkpoints
+
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tafl::ai::{AIKind, AIPlayer};
+use tafl::game::{Game, Outcome};
+use tafl::headless;
+use tafl::mcts;
+use tafl::nn::PolicyValueNet;
+use tafl::rng::Rng;
+
+struct Options {
+    iterations: usize,
+    games: usize,
+    simulations: u32,
+    max_plies: usize,
+    hidden: usize,
+    epochs: usize,
+    batch_size: usize,
+    learning_rate: f32,
+    eval_every: usize,
+    eval_games: usize,
+    seed: u64,
+    checkpoint_dir: PathBuf,
+    resume: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            iterations: 20,
+            games: 32,
+            simulations: 100,
+            max_plies: 150,
+            hidden: 64,
+            epochs: 4,
+            batch_size: 32,
+            learning_rate: 0.01,
+            eval_every: 5,
+            eval_games: 10,
+            seed: 0,
+            checkpoint_dir: PathBuf::from("checkpoints"),
+            resume: None,
+        }
+    }
+}
+
+const USAGE: &str = "usage: selfplay [--iterations N] [--games N] [--simulations N] \
+[--max-plies N] [--hidden N] [--epochs N] [--batch-size N] [--learning-rate F] \
+[--eval-every N] [--eval-games N] [--seed N] [--checkpoint-dir DIR] [--resume PATH]";
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {flag}"))?;
+        let bad_value = || format!("bad value for {flag}: {value}");
+        match flag.as_str() {
+            "--iterations" => options.iterations = value.parse().map_err(|_| bad_value())?,
+            "--games" => options.games = value.parse().map_err(|_| bad_value())?,
+            // one simulation only expands the root, leaving no visits to learn from
+            "--simulations" => {
+                options.simulations = value
+                    .parse()
+                    .ok()
+                    .filter(|&simulations| simulations >= 2)
+                    .ok_or_else(bad_value)?
+            }
+            "--max-plies" => options.max_plies = value.parse().map_err(|_| bad_value())?,
+            "--hidden" => options.hidden = value.parse().map_err(|_| bad_value())?,
+            "--epochs" => options.epochs = value.parse().map_err(|_| bad_value())?,
+            // batches of zero positions would never train
+            "--batch-size" => {
+                options.batch_size = value
+                    .parse()
+                    .ok()
+                    .filter(|&size| size > 0)
+                    .ok_or_else(bad_value)?
+            }
+            "--learning-rate" => options.learning_rate = value.parse().map_err(|_| bad_value())?,
+            "--eval-every" => options.eval_every = value.parse().map_err(|_| bad_value())?,
+            "--eval-games" => options.eval_games = value.parse().map_err(|_| bad_value())?,
+            "--seed" => options.seed = value.parse().map_err(|_| bad_value())?,
+            "--checkpoint-dir" => options.checkpoint_dir = PathBuf::from(value),
+            "--resume" => options.resume = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown option {flag}")),
+        }
+    }
+    Ok(options)
+}
+
+/// Plays the network against minimax, alternating sides.
+/// Returns wins, draws and losses for the network.
+fn evaluate(network: &Arc<PolicyValueNet>, options: &Options) -> (usize, usize, usize) {
+    let results = (0..options.eval_games)
+        .into_par_iter()
+        .map(|i| {
+            let mcts_player = Box::new(AIPlayer::new(AIKind::Mcts {
+                simulations: options.simulations,
+                network: Some(Arc::clone(network)),
+            }));
+            let minimax = Box::new(AIPlayer::new(AIKind::Minimax(4)));
+            let network_defends = i % 2 == 0;
+            let (defender, attacker) = if network_defends {
+                (mcts_player, minimax)
+            } else {
+                (minimax, mcts_player)
+            };
+            let played = headless::play_game(Game::new(), defender, attacker, options.max_plies)
+                .expect("AI players only make legal moves");
+            match played.outcome {
+                Some(Outcome::DefenderWin) if network_defends => (1, 0, 0),
+                Some(Outcome::AttackerWin) if !network_defends => (1, 0, 0),
+                Some(Outcome::Draw) | None => (0, 1, 0),
+                Some(_) => (0, 0, 1),
+            }
+        })
+        .collect::<Vec<_>>();
+    results
+        .into_iter()
+        .fold((0, 0, 0), |(w, d, l), (dw, dd, dl)| {
+            (w + dw, d + dd, l + dl)
+        })
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let mut network = match &options.resume {
+        Some(path) => PolicyValueNet::load(path).unwrap_or_else(|error| {
+            eprintln!("could not load {}: {error}", path.display());
+            std::process::exit(1);
+        }),
+        None => PolicyValueNet::new(options.hidden, options.seed),
+    };
+    if let Err(error) = std::fs::create_dir_all(&options.checkpoint_dir) {
+        eprintln!(
+            "could not create {}: {error}",
+            options.checkpoint_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let rng = Rng::new(options.seed);
+    for iteration in 1..=options.iterations {
+        let seeds = (0..options.games)
+            .map(|_| rng.next_u64())
+            .collect::<Vec<u64>>();
+        let mut samples = seeds
+            .into_par_iter()
+            .flat_map(|seed| {
+                mcts::self_play_game(
+                    Some(&network),
+                    options.simulations,
+                    options.max_plies,
+                    &Rng::new(seed),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut loss = 0.0;
+        for _ in 0..options.epochs {
+            for i in (1..samples.len()).rev() {
+                samples.swap(i, rng.below(i + 1));
+            }
+            let losses = samples
+                .chunks(options.batch_size)
+                .map(|batch| network.train_batch(batch, options.learning_rate))
+                .collect::<Vec<f32>>();
+            loss = losses.iter().sum::<f32>() / losses.len().max(1) as f32;
+        }
+        println!(
+            "iteration {iteration}: {} positions, loss {loss:.4}",
+            samples.len()
+        );
+
+        let checkpoint = options
+            .checkpoint_dir
+            .join(format!("iteration_{iteration:04}.nn"));
+        let latest = options.checkpoint_dir.join("latest.nn");
+        for path in [&checkpoint, &latest] {
+            if let Err(error) = network.save(path) {
+                eprintln!("could not save {}: {error}", path.display());
+                std::process::exit(1);
+            }
+        }
+
+        if options.eval_every > 0 && iteration % options.eval_every == 0 {
+            let (wins, draws, losses) = evaluate(&Arc::new(network.clone()), &options);
+            println!("  against Minimax(4): {wins} wins, {draws} draws, {losses} losses");
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Plays AI configurations against each other and reports results with Elo estimates
+//!
+//! cargo run --release --bin tournament -- --games 20 minimax:2 greedy random
+//! cargo run --release --bin tournament -- --mode gauntlet --sprt 0:20 new=minimax:3 old=minimax:2
+//!
+//! Engines are given as player specs (see `AIPlayer::from_spec`), optionally named with NAME=SPEC.
+
+use rayon::prelude::*;
+use tafl::ai::AIPlayer;
+use tafl::elo::{Score, Sprt, SprtResult};
+use tafl::game::{Game, Outcome};
+use tafl::headless;
+use tafl::rng::Rng;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// every engine plays every other engine
+    RoundRobin,
+    /// the first engine plays each of the others
+    Gauntlet,
+}
+
+struct Engine {
+    name: String,
+    /// built once so network weights are only loaded once
+    player: AIPlayer,
+}
+
+impl Engine {
+    /// a copy of the engine's player with its own random stream, so games differ
+    fn player(&self, seeds: &Rng) -> Box<AIPlayer> {
+        Box::new(AIPlayer {
+            rng: Rng::new(seeds.next_u64()),
+            ..self.player.clone()
+        })
+    }
+}
+
+struct Options {
+    mode: Mode,
+    games: u32,
+    max_plies: usize,
+    sprt: Option<Sprt>,
+    engines: Vec<Engine>,
+}
+
+const USAGE: &str = "usage: tournament [--mode round-robin|gauntlet] [--games N] \
+[--max-plies N] [--sprt ELO0:ELO1] [NAME=]SPEC [NAME=]SPEC...";
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        mode: Mode::RoundRobin,
+        games: 20,
+        max_plies: 200,
+        sprt: None,
+        engines: Vec::new(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            let (name, spec) = arg.split_once('=').unwrap_or((&arg, &arg));
+            options.engines.push(Engine {
+                name: name.to_string(),
+                player: AIPlayer::from_spec(spec)?,
+            });
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {arg}"))?;
+        let bad_value = || format!("bad value for {arg}: {value}");
+        match arg.as_str() {
+            "--mode" => {
+                options.mode = match value.as_str() {
+                    "round-robin" => Mode::RoundRobin,
+                    "gauntlet" => Mode::Gauntlet,
+                    _ => return Err(bad_value()),
+                }
+            }
+            "--games" => options.games = value.parse().map_err(|_| bad_value())?,
+            "--max-plies" => options.max_plies = value.parse().map_err(|_| bad_value())?,
+            "--sprt" => {
+                let (elo0, elo1) = value.split_once(':').ok_or_else(bad_value)?;
+                options.sprt = Some(Sprt {
+                    elo0: elo0.parse().map_err(|_| bad_value())?,
+                    elo1: elo1.parse().map_err(|_| bad_value())?,
+                    ..Sprt::default()
+                });
+            }
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    if options.engines.len() < 2 {
+        return Err("at least two engines are needed".to_string());
+    }
+    Ok(options)
+}
+
+/// Plays a game with each side defending once, each as (first, second) and scored for first
+fn play_pair(games: [(Box<AIPlayer>, Box<AIPlayer>); 2], max_plies: usize) -> Score {
+    let mut score = Score::default();
+    for ((first, second), first_defends) in games.into_iter().zip([true, false]) {
+        let (defender, attacker) = if first_defends {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let played = headless::play_game(Game::new(), defender, attacker, max_plies)
+            .expect("AI players only make legal moves");
+        match played.outcome {
+            Some(Outcome::DefenderWin) if first_defends => score.wins += 1,
+            Some(Outcome::AttackerWin) if !first_defends => score.wins += 1,
+            Some(Outcome::Draw) | None => score.draws += 1,
+            Some(_) => score.losses += 1,
+        }
+    }
+    score
+}
+
+/// Plays a match of up to options.games, stopping early if the SPRT decides
+fn play_match(first: &Engine, second: &Engine, options: &Options) -> Score {
+    let pairs = options.games.div_ceil(2);
+    // enough pairs at a time to keep every thread busy
+    let batch = rayon::current_num_threads() as u32;
+    let seeds = Rng::from_entropy();
+    let mut score = Score::default();
+    let mut played = 0;
+    while played < pairs {
+        let count = batch.min(pairs - played);
+        let games = (0..count)
+            .map(|_| {
+                [
+                    (first.player(&seeds), second.player(&seeds)),
+                    (first.player(&seeds), second.player(&seeds)),
+                ]
+            })
+            .collect::<Vec<_>>();
+        // the engines' players aren't shared between threads, only their copies
+        let max_plies = options.max_plies;
+        let results = games
+            .into_par_iter()
+            .map(|games| play_pair(games, max_plies))
+            .collect::<Vec<Score>>();
+        results.into_iter().for_each(|result| score.add(result));
+        played += count;
+
+        if let Some(sprt) = &options.sprt {
+            match sprt.test(&score) {
+                SprtResult::Continue => {}
+                result => {
+                    let verdict = match result {
+                        SprtResult::Accept => "H1 accepted",
+                        _ => "H0 accepted",
+                    };
+                    println!(
+                        "  SPRT {verdict} after {} games (llr {:.2})",
+                        score.games(),
+                        sprt.llr(&score)
+                    );
+                    break;
+                }
+            }
+        }
+    }
+    score
+}
+
+fn elo_text(score: &Score) -> String {
+    score
+        .elo()
+        .map_or_else(|| "-".to_string(), |elo| elo.to_string())
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let engines = &options.engines;
+    let pairings = match options.mode {
+        Mode::RoundRobin => (0..engines.len())
+            .flat_map(|i| (i + 1..engines.len()).map(move |j| (i, j)))
+            .collect::<Vec<(usize, usize)>>(),
+        Mode::Gauntlet => (1..engines.len()).map(|j| (0, j)).collect(),
+    };
+
+    let mut totals = vec![Score::default(); engines.len()];
+    println!(
+        "{:<16} {:<16} {:>16} {:>20}",
+        "engine", "opponent", "w/d/l", "elo"
+    );
+    for (i, j) in pairings {
+        let score = play_match(&engines[i], &engines[j], &options);
+        totals[i].add(score);
+        totals[j].add(score.reversed());
+        println!(
+            "{:<16} {:<16} {:>16} {:>20}",
+            engines[i].name,
+            engines[j].name,
+            score.to_string(),
+            elo_text(&score)
+        );
+    }
+
+    println!("\nstandings, elo against the field");
+    let mut standings = engines.iter().zip(totals).collect::<Vec<_>>();
+    standings.sort_by(|(_, a), (_, b)| b.ratio().total_cmp(&a.ratio()));
+    for (engine, score) in standings {
+        println!(
+            "{:<16} {:>16} {:>7.1}% {:>20}",
+            engine.name,
+            score.to_string(),
+            score.ratio() * 100.0,
+            elo_text(&score)
+        );
+    }
+}
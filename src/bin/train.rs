@@ -0,0 +1,113 @@
+//! Trains a value network on minimax self-play games
+//!
+//! cargo run --release --bin train -- --games 200 --out tafl.nn
+
+use tafl::nn::{self, Network};
+use tafl::rng::Rng;
+
+struct Options {
+    games: usize,
+    depth: u32,
+    max_plies: usize,
+    hidden: usize,
+    epochs: usize,
+    batch_size: usize,
+    learning_rate: f32,
+    seed: u64,
+    out: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            games: 100,
+            depth: 1,
+            max_plies: 200,
+            hidden: 64,
+            epochs: 20,
+            batch_size: 32,
+            learning_rate: 0.01,
+            seed: 0,
+            out: "tafl.nn".to_string(),
+        }
+    }
+}
+
+const USAGE: &str = "usage: train [--games N] [--depth N] [--max-plies N] [--hidden N] \
+[--epochs N] [--batch-size N] [--learning-rate F] [--seed N] [--out PATH]";
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {flag}"))?;
+        let bad_value = || format!("bad value for {flag}: {value}");
+        match flag.as_str() {
+            "--games" => options.games = value.parse().map_err(|_| bad_value())?,
+            "--depth" => options.depth = value.parse().map_err(|_| bad_value())?,
+            "--max-plies" => options.max_plies = value.parse().map_err(|_| bad_value())?,
+            "--hidden" => options.hidden = value.parse().map_err(|_| bad_value())?,
+            "--epochs" => options.epochs = value.parse().map_err(|_| bad_value())?,
+            // batches of zero positions would never train
+            "--batch-size" => {
+                options.batch_size = value
+                    .parse()
+                    .ok()
+                    .filter(|&size| size > 0)
+                    .ok_or_else(bad_value)?
+            }
+            "--learning-rate" => options.learning_rate = value.parse().map_err(|_| bad_value())?,
+            "--seed" => options.seed = value.parse().map_err(|_| bad_value())?,
+            "--out" => options.out = value,
+            _ => return Err(format!("unknown option {flag}")),
+        }
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    println!(
+        "playing {} self-play games at depth {}",
+        options.games, options.depth
+    );
+    let mut samples = nn::self_play(
+        options.games,
+        options.depth,
+        options.max_plies,
+        options.seed,
+    );
+    println!("collected {} positions", samples.len());
+
+    let rng = Rng::new(options.seed);
+    let mut network = Network::new(options.hidden, options.seed);
+    for epoch in 1..=options.epochs {
+        // shuffle so batches mix positions from different games
+        for i in (1..samples.len()).rev() {
+            samples.swap(i, rng.below(i + 1));
+        }
+        let losses = samples
+            .chunks(options.batch_size)
+            .map(|batch| network.train_batch(batch, options.learning_rate))
+            .collect::<Vec<f32>>();
+        let loss = losses.iter().sum::<f32>() / losses.len().max(1) as f32;
+        println!("epoch {epoch} loss {loss:.4}");
+    }
+
+    match network.save(&options.out) {
+        Ok(()) => println!("saved weights to {}", options.out),
+        Err(error) => {
+            eprintln!("could not save weights to {}: {error}", options.out);
+            std::process::exit(1);
+        }
+    }
+}
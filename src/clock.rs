@@ -0,0 +1,422 @@
+//! Game clocks for timed games
+//!
+//! Time controls are written as seconds: `300` for sudden death, `300+5` for
+//! five seconds added after each move, and `300:30x3` for byoyomi, three
+//! periods of thirty seconds once the main time runs out.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeControl {
+    /// the whole game in this time
+    SuddenDeath(Duration),
+    /// `increment` added after each move
+    Increment { base: Duration, increment: Duration },
+    /// Once `base` is used up each move has to be made within `period`.
+    /// Going over uses up a period and losing the last one loses the game.
+    Byoyomi {
+        base: Duration,
+        period: Duration,
+        periods: u32,
+    },
+}
+
+impl TimeControl {
+    pub fn base(&self) -> Duration {
+        match *self {
+            TimeControl::SuddenDeath(base) => base,
+            TimeControl::Increment { base, .. } | TimeControl::Byoyomi { base, .. } => base,
+        }
+    }
+}
+
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+    text.parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("not a number of seconds: {text}"))
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let control = if let Some((base, increment)) = s.split_once('+') {
+            TimeControl::Increment {
+                base: parse_seconds(base)?,
+                increment: parse_seconds(increment)?,
+            }
+        } else if let Some((base, byoyomi)) = s.split_once(':') {
+            let (period, periods) = byoyomi.split_once('x').unwrap_or((byoyomi, "1"));
+            let periods = periods
+                .parse()
+                .ok()
+                .filter(|&periods| periods > 0)
+                .ok_or_else(|| format!("not a number of periods: {periods}"))?;
+            TimeControl::Byoyomi {
+                base: parse_seconds(base)?,
+                period: parse_seconds(period)?,
+                periods,
+            }
+        } else {
+            TimeControl::SuddenDeath(parse_seconds(s)?)
+        };
+        if Clock::new(control).attacker.flagged() {
+            return Err(format!("no time to make a move with {s}"));
+        }
+        Ok(control)
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = |duration: Duration| duration.as_secs_f64();
+        match *self {
+            TimeControl::SuddenDeath(base) => write!(f, "{}", seconds(base)),
+            TimeControl::Increment { base, increment } => {
+                write!(f, "{}+{}", seconds(base), seconds(increment))
+            }
+            TimeControl::Byoyomi {
+                base,
+                period,
+                periods,
+            } => write!(f, "{}:{}x{periods}", seconds(base), seconds(period)),
+        }
+    }
+}
+
+/// Time left for one side
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SideClock {
+    /// main time, or what is left of the current byoyomi period
+    pub remaining: Duration,
+    /// byoyomi periods not yet used up, including the current one
+    pub periods: u32,
+    /// whether the main time has run out and byoyomi has started
+    pub overtime: bool,
+}
+
+impl SideClock {
+    /// out of time, losing the game
+    pub fn flagged(&self) -> bool {
+        self.remaining.is_zero()
+    }
+
+    /// takes thinking time off, moving on to byoyomi periods as each runs out
+    pub fn spend(&mut self, control: &TimeControl, mut elapsed: Duration) {
+        while !self.flagged() {
+            if elapsed < self.remaining {
+                self.remaining -= elapsed;
+                return;
+            }
+            elapsed -= self.remaining;
+            self.remaining = Duration::ZERO;
+            if let TimeControl::Byoyomi { period, .. } = *control {
+                if self.overtime {
+                    self.periods -= 1;
+                }
+                self.overtime = true;
+                if self.periods > 0 {
+                    self.remaining = period;
+                }
+            }
+        }
+    }
+
+    /// after a move in time, adding any increment or starting a fresh period
+    pub fn moved(&mut self, control: &TimeControl) {
+        if self.flagged() {
+            return;
+        }
+        match *control {
+            TimeControl::SuddenDeath(_) => (),
+            TimeControl::Increment { increment, .. } => self.remaining += increment,
+            TimeControl::Byoyomi { period, .. } => {
+                if self.overtime {
+                    self.remaining = period;
+                }
+            }
+        }
+    }
+
+    /// How long to spend on the next move, a share of what is left
+    /// plus most of what will be given back after it
+    pub fn budget(&self, control: &TimeControl) -> Duration {
+        let share = self.remaining / 30;
+        match *control {
+            TimeControl::SuddenDeath(_) => share,
+            TimeControl::Increment { increment, .. } => {
+                (share + increment * 3 / 4).min(self.remaining / 2)
+            }
+            TimeControl::Byoyomi { period, .. } if self.overtime => period * 3 / 4,
+            TimeControl::Byoyomi { period, .. } => share + period * 3 / 4,
+        }
+    }
+}
+
+/// Shown like `4:05`, with tenths under ten seconds and byoyomi periods like `0:25 x3`
+impl Display for SideClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // cut down to tenths first so 9.96 isn't rounded up past the switch to whole seconds
+        let tenths = self.remaining.as_millis() / 100;
+        let seconds = self.remaining.as_secs();
+        if tenths < 100 {
+            write!(f, "0:{:02}.{}", tenths / 10, tenths % 10)?;
+        } else {
+            write!(f, "{}:{:02}", seconds / 60, seconds % 60)?;
+        }
+        if self.overtime {
+            write!(f, " x{}", self.periods)?;
+        }
+        Ok(())
+    }
+}
+
+/// Both sides' time in a timed game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clock {
+    pub control: TimeControl,
+    pub attacker: SideClock,
+    pub defender: SideClock,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let mut side = SideClock {
+            remaining: control.base(),
+            periods: 0,
+            overtime: false,
+        };
+        match control {
+            // without main time the first move is already in byoyomi
+            TimeControl::Byoyomi {
+                base,
+                period,
+                periods,
+            } if base.is_zero() => {
+                side.remaining = period;
+                side.periods = periods;
+                side.overtime = true;
+            }
+            TimeControl::Byoyomi { periods, .. } => side.periods = periods,
+            // or has just the increment, as every later move does
+            TimeControl::Increment { base, increment } if base.is_zero() => {
+                side.remaining = increment;
+            }
+            _ => (),
+        }
+        Clock {
+            control,
+            attacker: side,
+            defender: side,
+        }
+    }
+
+    pub fn side(&self, defender: bool) -> &SideClock {
+        if defender {
+            &self.defender
+        } else {
+            &self.attacker
+        }
+    }
+
+    /// Takes thinking time off one side
+    pub fn spend(&mut self, defender: bool, elapsed: Duration) {
+        let control = self.control;
+        self.side_mut(defender).spend(&control, elapsed);
+    }
+
+    /// after one side's move
+    pub fn moved(&mut self, defender: bool) {
+        let control = self.control;
+        self.side_mut(defender).moved(&control);
+    }
+
+    pub fn budget(&self, defender: bool) -> Duration {
+        self.side(defender).budget(&self.control)
+    }
+
+    fn side_mut(&mut self, defender: bool) -> &mut SideClock {
+        if defender {
+            &mut self.defender
+        } else {
+            &mut self.attacker
+        }
+    }
+}
+
+/// Seconds from a fixed point, for timing moves and searches.
+/// `Instant::now` panics in the browser so the time comes from the page there instead.
+/// A browser build without `gui` has no page to ask, so time stands still: clocks
+/// never run down and searches stop at their depth or simulations rather than a time limit.
+pub fn now() -> f64 {
+    #[cfg(all(target_arch = "wasm32", feature = "gui"))]
+    return macroquad::miniquad::date::now();
+    // see above, there is nothing to tell the time by
+    #[cfg(all(target_arch = "wasm32", not(feature = "gui")))]
+    return 0.0;
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        START
+            .get_or_init(std::time::Instant::now)
+            .elapsed()
+            .as_secs_f64()
+    }
+}
+
+/// A clock running against a source of time, as used by `GameState`
+pub struct Timer {
+    /// as it was when the current turn started
+    pub clock: Clock,
+    /// seconds from any fixed point, such as `now`
+    now: Box<dyn Fn() -> f64>,
+    turn_start: f64,
+}
+
+impl Timer {
+    pub fn new(clock: Clock, now: impl Fn() -> f64 + 'static) -> Self {
+        Timer {
+            clock,
+            turn_start: now(),
+            now: Box::new(now),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::try_from_secs_f64((self.now)() - self.turn_start).unwrap_or_default()
+    }
+
+    /// the clock with the thinking time so far taken off the side to move
+    pub fn live(&self, defenders_turn: bool) -> Clock {
+        let mut clock = self.clock;
+        clock.spend(defenders_turn, self.elapsed());
+        clock
+    }
+
+    /// Charges the side to move for its turn and starts the other side's.
+    /// True if the side ran out of time before moving.
+    pub fn end_turn(&mut self, defenders_turn: bool) -> bool {
+        self.clock = self.live(defenders_turn);
+        self.restart_turn();
+        let flagged = self.clock.side(defenders_turn).flagged();
+        self.clock.moved(defenders_turn);
+        flagged
+    }
+
+    /// starts timing the turn again from now, leaving the clock as it is
+    pub fn restart_turn(&mut self) {
+        self.turn_start = (self.now)();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn time_controls() {
+        for text in ["300", "300+5", "600:30x3", "2.5+0.5"] {
+            let control = text.parse::<TimeControl>().unwrap();
+            assert_eq!(control.to_string(), text);
+        }
+        assert_eq!(
+            "60:10".parse(),
+            Ok(TimeControl::Byoyomi {
+                base: secs(60),
+                period: secs(10),
+                periods: 1
+            })
+        );
+        for bad in ["", "five", "300+", "300:30x0", "-1", "0", "0+0", "0:0x3"] {
+            assert!(bad.parse::<TimeControl>().is_err(), "{bad} parsed");
+        }
+    }
+
+    #[test]
+    fn increments_and_byoyomi() {
+        let mut clock = Clock::new("60+5".parse().unwrap());
+        clock.spend(false, secs(10));
+        clock.moved(false);
+        assert_eq!(clock.attacker.remaining, secs(55));
+        assert_eq!(clock.defender.remaining, secs(60));
+        clock.spend(true, secs(60));
+        assert!(clock.defender.flagged());
+
+        // main time runs into the first period, then a whole period is lost
+        let mut clock = Clock::new("60:10x2".parse().unwrap());
+        clock.spend(false, secs(65));
+        assert!(clock.attacker.overtime);
+        assert_eq!(clock.attacker.remaining, secs(5));
+        clock.moved(false);
+        assert_eq!(clock.attacker.remaining, secs(10));
+        clock.spend(false, secs(12));
+        assert_eq!(
+            (clock.attacker.periods, clock.attacker.remaining),
+            (1, secs(8))
+        );
+        assert_eq!(clock.attacker.to_string(), "0:08.0 x1");
+        clock.spend(false, secs(8));
+        assert!(clock.attacker.flagged());
+
+        let sudden = Clock::new("300".parse().unwrap());
+        assert_eq!(sudden.attacker.to_string(), "5:00");
+        assert_eq!(sudden.budget(false), secs(10));
+    }
+
+    #[test]
+    fn no_main_time() {
+        // straight into byoyomi, losing a period only when one runs out
+        let mut clock = Clock::new("0:30x3".parse().unwrap());
+        assert!(clock.attacker.overtime && !clock.attacker.flagged());
+        assert_eq!(clock.attacker.to_string(), "0:30 x3");
+        clock.spend(false, secs(20));
+        clock.moved(false);
+        assert_eq!(
+            (clock.attacker.periods, clock.attacker.remaining),
+            (3, secs(30))
+        );
+
+        let mut clock = Clock::new("0+5".parse().unwrap());
+        clock.spend(false, secs(4));
+        assert!(!clock.attacker.flagged());
+        clock.moved(false);
+        assert_eq!(clock.attacker.remaining, secs(6));
+    }
+
+    #[test]
+    fn tenths_near_ten_seconds() {
+        let side = |millis| SideClock {
+            remaining: Duration::from_millis(millis),
+            periods: 0,
+            overtime: false,
+        };
+        assert_eq!(side(9_970).to_string(), "0:09.9");
+        assert_eq!(side(10_000).to_string(), "0:10");
+        assert_eq!(side(50).to_string(), "0:00.0");
+    }
+
+    #[test]
+    fn timer_charges_turns() {
+        let now = Rc::new(Cell::new(100.0));
+        let time = Rc::clone(&now);
+        let mut timer = Timer::new(Clock::new("30+2".parse().unwrap()), move || time.get());
+        now.set(110.0);
+        assert_eq!(timer.live(false).attacker.remaining, secs(20));
+        assert!(!timer.end_turn(false));
+        assert_eq!(timer.clock.attacker.remaining, secs(22));
+
+        // the defenders' turn started when the attackers moved
+        now.set(150.0);
+        assert!(timer.live(true).defender.flagged());
+        assert!(timer.end_turn(true));
+    }
+}
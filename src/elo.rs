@@ -0,0 +1,203 @@
+//! Match statistics for comparing engines: Elo estimates and SPRT
+
+/// Wins, draws and losses from one engine's point of view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Elo difference with the half width of its 95% confidence interval
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Elo {
+    pub diff: f64,
+    pub error: f64,
+}
+
+/// Outcome of a sequential probability ratio test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+    /// elo1 hypothesis accepted
+    Accept,
+    /// elo0 hypothesis accepted
+    Reject,
+    Continue,
+}
+
+/// Tests whether an engine is elo1 rather than elo0 stronger,
+/// with false positive rate alpha and false negative rate beta
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn add(&mut self, other: Score) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    /// the same games seen from the opponent's side
+    pub fn reversed(&self) -> Score {
+        Score {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        }
+    }
+
+    /// mean points per game, a draw is half a point
+    pub fn ratio(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// variance of the points of a single game
+    fn variance(&self) -> f64 {
+        let games = self.games() as f64;
+        let mean = self.ratio();
+        let wins = self.wins as f64 / games;
+        let draws = self.draws as f64 / games;
+        let losses = self.losses as f64 / games;
+        wins * (1.0 - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2)
+    }
+
+    /// Elo estimate, None before any games are played.
+    /// A perfect or zero score gives an infinite difference.
+    pub fn elo(&self) -> Option<Elo> {
+        if self.games() == 0 {
+            return None;
+        }
+        let ratio = self.ratio();
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = elo_from_ratio((ratio - 1.96 * deviation).max(0.0));
+        let high = elo_from_ratio((ratio + 1.96 * deviation).min(1.0));
+        Some(Elo {
+            diff: elo_from_ratio(ratio),
+            error: (high - low) / 2.0,
+        })
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+impl std::fmt::Display for Elo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.diff.is_finite() && self.error.is_finite() {
+            write!(f, "{:+.1} +/- {:.1}", self.diff, self.error)
+        } else if self.diff.is_finite() {
+            write!(f, "{:+.1} +/- inf", self.diff)
+        } else {
+            write!(f, "{}inf", if self.diff > 0.0 { "+" } else { "-" })
+        }
+    }
+}
+
+pub fn elo_from_ratio(ratio: f64) -> f64 {
+    -400.0 * (1.0 / ratio - 1.0).log10()
+}
+
+pub fn ratio_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    /// Log likelihood ratio, using the normal approximation of the score
+    pub fn llr(&self, score: &Score) -> f64 {
+        let variance = score.variance();
+        if score.games() == 0 || variance == 0.0 {
+            return 0.0;
+        }
+        let s0 = ratio_from_elo(self.elo0);
+        let s1 = ratio_from_elo(self.elo1);
+        let variance_of_mean = variance / score.games() as f64;
+        (s1 - s0) * (2.0 * score.ratio() - s0 - s1) / (2.0 * variance_of_mean)
+    }
+
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn test(&self, score: &Score) -> SprtResult {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtResult::Accept
+        } else if llr <= lower {
+            SprtResult::Reject
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn elo_estimates() {
+        let even = Score {
+            wins: 10,
+            draws: 0,
+            losses: 10,
+        };
+        let elo = even.elo().unwrap();
+        assert!(elo.diff.abs() < 1e-9);
+        assert!(elo.error > 100.0 && elo.error < 200.0);
+
+        // 75% is close to +191 Elo
+        let strong = Score {
+            wins: 70,
+            draws: 10,
+            losses: 20,
+        };
+        assert!((strong.elo().unwrap().diff - 190.8).abs() < 0.1);
+        assert!((ratio_from_elo(elo_from_ratio(0.3)) - 0.3).abs() < 1e-9);
+        assert_eq!(Score::default().elo(), None);
+    }
+
+    #[test]
+    fn sprt_decides() {
+        let sprt = Sprt::default();
+        let winning = Score {
+            wins: 300,
+            draws: 100,
+            losses: 100,
+        };
+        assert_eq!(sprt.test(&winning), SprtResult::Accept);
+        assert_eq!(sprt.test(&winning.reversed()), SprtResult::Reject);
+        let close = Score {
+            wins: 5,
+            draws: 2,
+            losses: 5,
+        };
+        assert_eq!(sprt.test(&close), SprtResult::Continue);
+    }
+}
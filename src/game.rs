@@ -1,10 +1,58 @@
+use crate::clock::{Clock, Timer};
 use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
 
-use crate::ai::AIPlayer;
-use crate::human::HumanPlayer;
-pub enum Player {
-    Human(HumanPlayer),
-    AI(AIPlayer),
+pub type MoveFuture<'a> = Pin<Box<dyn Future<Output = Option<Move>> + 'a>>;
+
+/// One side of a `GameState`, such as a human at the display or an AI
+pub trait Player {
+    /// The move to play in `game`, where it is always this player's turn.
+    /// None if the player stopped without moving, for example a script
+    /// running out of moves, and the turn should be asked for again later.
+    fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a>;
+
+    /// called with the new position after the other player moves
+    fn opponent_moved(&mut self, _game: &Game, _mv: Move) {}
+
+    /// called once with the final position
+    fn game_ended(&mut self, _game: &Game) {}
+
+    /// called when the position changes other than by a move, like an undo
+    fn position_changed(&mut self, _game: &Game) {}
+
+    /// called before `choose_move` in a timed game with how long the move should take
+    fn time_budget(&mut self, _budget: Duration) {}
+
+    /// false for players keeping their own copy of the game that can't follow
+    /// an undo, like a remote player, which turns off undo and redo
+    fn allows_undo(&self) -> bool {
+        true
+    }
+
+    /// How the player rates `game`, from -1 for the attackers winning to 1 for
+    /// the defenders. None for players that don't evaluate positions, like humans
+    fn evaluate(&self, _game: &Game) -> Option<f32> {
+        None
+    }
+
+    /// why the player stopped choosing moves, such as a lost connection
+    fn error(&self) -> Option<String> {
+        None
+    }
+
+    /// whether undoing a turn should stop at this player
+    fn is_human(&self) -> bool {
+        false
+    }
+
+    /// description saved with a game so the player can be recreated when it's
+    /// loaded, like `human` or `minimax:4`
+    fn spec(&self) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
@@ -27,7 +75,85 @@ impl Display for PieceType {
     }
 }
 
-#[derive(Clone)]
+/// Rule set and board being played, brandubh is the only one so far
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Variant {
+    Brandubh,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 1] = [Variant::Brandubh];
+
+    pub fn board_size(&self) -> usize {
+        match self {
+            Variant::Brandubh => BOARD_SIZE,
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Variant::Brandubh => write!(f, "brandubh"),
+        }
+    }
+}
+
+impl std::str::FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Variant::ALL
+            .into_iter()
+            .find(|variant| variant.to_string() == s.to_lowercase())
+            .ok_or_else(|| format!("unknown variant {s}"))
+    }
+}
+
+/// How a finished game ended
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    DefenderWin,
+    AttackerWin,
+    Draw,
+}
+
+/// Why a finished game ended
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum EndReason {
+    KingEscaped,
+    KingCaptured,
+    /// the losing side had no legal moves
+    NoMoves,
+    /// the losing side ran out of time
+    FlagFell,
+}
+
+impl Display for EndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndReason::KingEscaped => write!(f, "the king escaped"),
+            EndReason::KingCaptured => write!(f, "the king was captured"),
+            EndReason::NoMoves => write!(f, "no moves left"),
+            EndReason::FlagFell => write!(f, "time ran out"),
+        }
+    }
+}
+
+/// What happened in a finished game, from `GameState::summary`
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct GameSummary {
+    pub outcome: Outcome,
+    pub reason: EndReason,
+    /// plies played
+    pub moves: usize,
+    /// attackers taken by the defenders
+    pub attackers_captured: usize,
+    /// defenders taken by the attackers, not counting the king
+    pub defenders_captured: usize,
+}
+
+#[derive(Clone, Debug)]
 pub struct Game {
     pub board: Board,
     pub defenders_turn: bool,
@@ -39,15 +165,55 @@ pub struct GameState {
     pub game: Game,
     // used for highlighting options and moving pieces
     pub current_selection: Option<Tile>,
-    defender_player: Player,
-    attacker_player: Player,
+    defender_player: Box<dyn Player>,
+    attacker_player: Box<dyn Player>,
+    start: Game,
+    history: Vec<MoveReport>,
+    /// undone moves, the next one to redo last
+    undone: Vec<Move>,
+    /// None for an untimed game
+    timer: Option<Timer>,
+    /// the clock as each turn started, by ply. None before the clocks were set,
+    /// such as the moves of a resumed game, as the time then isn't known
+    clocks: Vec<Option<Clock>>,
+    /// whether the game ended by the side to move running out of time
+    flag_fell: bool,
 }
 
-pub struct Action {
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Move {
     pub src: Tile,
     pub dest: Tile,
 }
 
+impl From<(Tile, Tile)> for Move {
+    fn from((src, dest): (Tile, Tile)) -> Self {
+        Move { src, dest }
+    }
+}
+
+/// What a move did, from `Game::apply_move`
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct MoveReport {
+    pub mv: Move,
+    /// tiles of the pieces taken, a captured king ends the game but stays on the board
+    pub captures: Vec<Tile>,
+}
+
+/// A player asked for a move that is not allowed
+#[derive(Debug, PartialEq, Eq)]
+pub struct IllegalMove {
+    pub mv: Move,
+}
+
+impl Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "illegal move {}", self.mv)
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct Tile {
     pub c: usize,
@@ -69,7 +235,7 @@ impl From<(i32, i32)> for Tile {
     }
 }
 
-const BOARD_SIZE: usize = 7;
+pub const BOARD_SIZE: usize = 7;
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct Board(pub [[PieceType; BOARD_SIZE]; BOARD_SIZE]);
 
@@ -87,7 +253,6 @@ impl Board {
             [B, B, B, A, B, B, B],
         ])
     }
-    #[cfg(test)]
     pub fn empty() -> Board {
         use PieceType::Blank as B;
         Board([
@@ -107,14 +272,19 @@ impl Display for Board {
         let mut board_str = String::new();
         let size = self.0.len();
         for r in 0..size {
-            board_str.push('|');
+            // rank labels, matching the move notation
+            board_str.push_str(&format!("{:>2} |", size - r));
             for c in 0..size {
                 // print flipped to match display
                 board_str.push_str(&format!("{}|", self.0[c][r]));
             }
             board_str.push('\n');
         }
-        write!(f, "{}", board_str)
+        board_str.push_str("    ");
+        for file in (b'a'..).take(size) {
+            board_str.push_str(&format!("{} ", file as char));
+        }
+        writeln!(f, "{}", board_str.trim_end())
     }
 }
 
@@ -129,31 +299,389 @@ impl Default for Game {
 }
 
 impl GameState {
-    pub fn new(defender: Player, attacker: Player) -> Self {
+    pub fn new(defender: Box<dyn Player>, attacker: Box<dyn Player>) -> Self {
+        Self::from_game(Game::new(), defender, attacker)
+    }
+
+    /// continues play from the given position
+    pub fn from_game(game: Game, defender: Box<dyn Player>, attacker: Box<dyn Player>) -> Self {
         GameState {
-            game: Game::new(),
+            start: game.clone(),
+            game,
             current_selection: None,
             defender_player: defender,
             attacker_player: attacker,
+            history: Vec::new(),
+            undone: Vec::new(),
+            timer: None,
+            clocks: Vec::new(),
+            flag_fell: false,
+        }
+    }
+
+    /// Times the rest of the game from `clock`, such as `Clock::new` for full clocks
+    /// or one saved part way through. `now` gives the time in seconds, usually `clock::now`.
+    /// Going back before this move leaves the clocks as they are, unless
+    /// they are given by `set_past_clocks`.
+    pub fn set_clock(&mut self, clock: Clock, now: impl Fn() -> f64 + 'static) {
+        let timer = Timer::new(clock, now);
+        self.clocks = vec![None; self.history.len()];
+        self.clocks.push(Some(timer.clock));
+        self.timer = Some(timer);
+    }
+
+    /// The clock as each move so far was started, None where it isn't known
+    pub fn past_clocks(&self) -> Vec<Option<Clock>> {
+        (0..self.history.len())
+            .map(|ply| self.clocks.get(ply).copied().flatten())
+            .collect()
+    }
+
+    /// Sets the clocks the moves so far were started with, as from `past_clocks`,
+    /// so undoing in a resumed game puts the time back. Follows `set_clock`.
+    pub fn set_past_clocks(&mut self, past: &[Option<Clock>]) {
+        for (entry, &clock) in self.clocks.iter_mut().zip(past).take(self.history.len()) {
+            *entry = clock;
+        }
+    }
+
+    /// whether the game ended by running out of time
+    pub fn flag_fell(&self) -> bool {
+        self.flag_fell
+    }
+
+    /// Both sides' time, counting down for the side to move. None if untimed
+    pub fn clock(&self) -> Option<Clock> {
+        let timer = self.timer.as_ref()?;
+        if self.game.game_over {
+            Some(timer.clock)
+        } else {
+            Some(timer.live(self.game.defenders_turn))
+        }
+    }
+
+    /// Continues a game part way through, with the moves played so far and
+    /// the moves that have been undone, next to redo first
+    pub fn from_history(
+        start: Game,
+        moves: &[Move],
+        undone: &[Move],
+        defender: Box<dyn Player>,
+        attacker: Box<dyn Player>,
+    ) -> Result<Self, IllegalMove> {
+        let mut state = GameState::from_game(start, defender, attacker);
+        for &mv in moves {
+            if !state.game.is_valid_move(mv) {
+                return Err(IllegalMove { mv });
+            }
+            let (game, report) = state.game.apply_move(mv);
+            state.game = game;
+            state.history.push(report);
+        }
+        // undone moves still have to follow on from the position
+        let mut game = state.game.clone();
+        for &mv in undone {
+            if !game.is_valid_move(mv) {
+                return Err(IllegalMove { mv });
+            }
+            game = game.apply_move(mv).0;
         }
+        state.undone = undone.iter().rev().copied().collect();
+        Ok(state)
     }
 
     // take player turn
-    pub async fn next_turn(&mut self) {
-        let (src, dest) = match self.current_player() {
-            Player::Human(human) => human.player_turn(&self.game).await,
-            Player::AI(ai) => ai.take_turn(&self.game),
+    // returns what the move did, None if the player did not move
+    pub async fn next_turn(&mut self) -> Result<Option<MoveReport>, IllegalMove> {
+        let defenders_turn = self.game.defenders_turn;
+        let player = if defenders_turn {
+            &mut self.defender_player
+        } else {
+            &mut self.attacker_player
+        };
+        if let Some(timer) = &self.timer {
+            player.time_budget(timer.live(defenders_turn).budget(defenders_turn));
+        }
+
+        // stop waiting for the move if the player's time runs out first
+        let timer = &self.timer;
+        let flagged = || {
+            timer
+                .as_ref()
+                .is_some_and(|timer| timer.live(defenders_turn).side(defenders_turn).flagged())
+        };
+        let mut future = player.choose_move(&self.game);
+        let chosen = std::future::poll_fn(|context| match future.as_mut().poll(context) {
+            Poll::Ready(mv) => Poll::Ready(Some(mv)),
+            Poll::Pending if flagged() => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        })
+        .await;
+        drop(future);
+        let mv = match chosen {
+            Some(Some(mv)) => mv,
+            Some(None) => return Ok(None),
+            None => {
+                self.flag_fall();
+                return Ok(None);
+            }
+        };
+        // a move made after the flag fell, like from a long AI search, doesn't count
+        if let Some(timer) = &mut self.timer {
+            if timer.end_turn(defenders_turn) {
+                self.flag_fall();
+                return Ok(None);
+            }
+        }
+        if !self.game.is_valid_move(mv) {
+            return Err(IllegalMove { mv });
+        }
+        let (game, report) = self.game.apply_move(mv); // this call also changes turn
+        self.game = game;
+        self.history.push(report.clone());
+        self.undone.clear();
+        if let Some(timer) = &self.timer {
+            self.clocks.truncate(self.history.len());
+            self.clocks.push(Some(timer.clock));
+        }
+
+        let opponent = if self.game.defenders_turn {
+            &mut self.defender_player
+        } else {
+            &mut self.attacker_player
         };
-        self.game = self.game.gen_next(src, dest); // this call also changes turn
+        opponent.opponent_moved(&self.game, mv);
+        if self.game.game_over {
+            self.notify_game_ended();
+        }
+        Ok(Some(report))
+    }
+
+    /// Ends the game with the side to move losing on time, as `next_turn` does
+    /// when its clock runs out
+    pub fn flag_fall(&mut self) {
+        if let Some(timer) = &mut self.timer {
+            timer.clock = timer.live(self.game.defenders_turn);
+        }
+        self.game.game_over = true;
+        self.game.defender_won = !self.game.defenders_turn;
+        self.flag_fell = true;
+        self.notify_game_ended();
     }
 
-    pub fn current_player(&self) -> &Player {
+    /// tells both players the game is over, done by next_turn when a move ends the game
+    pub fn notify_game_ended(&mut self) {
+        self.defender_player.game_ended(&self.game);
+        self.attacker_player.game_ended(&self.game);
+    }
+
+    pub fn current_player(&self) -> &dyn Player {
         if self.game.defenders_turn {
-            &self.defender_player
+            self.defender_player.as_ref()
         } else {
-            &self.attacker_player
+            self.attacker_player.as_ref()
         }
     }
+
+    pub fn defender(&self) -> &dyn Player {
+        self.defender_player.as_ref()
+    }
+
+    pub fn attacker(&self) -> &dyn Player {
+        self.attacker_player.as_ref()
+    }
+
+    /// the defender and attacker, such as to play them again
+    pub fn into_players(self) -> (Box<dyn Player>, Box<dyn Player>) {
+        (self.defender_player, self.attacker_player)
+    }
+
+    /// position the game started from
+    pub fn start(&self) -> &Game {
+        &self.start
+    }
+
+    /// moves played from the start to the current position
+    pub fn history(&self) -> &[MoveReport] {
+        &self.history
+    }
+
+    /// moves that can be redone, the next one last
+    pub fn undone(&self) -> &[Move] {
+        &self.undone
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty() && self.allows_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty() && self.allows_undo()
+    }
+
+    /// Takes back the last move, returning it. Never done with a player that
+    /// doesn't allow it, such as a remote one
+    pub fn undo(&mut self) -> Option<Move> {
+        if !self.allows_undo() {
+            return None;
+        }
+        let mv = self.history.pop()?.mv;
+        self.undone.push(mv);
+        self.game = self
+            .history
+            .iter()
+            .fold(self.start.clone(), |game, report| {
+                game.apply_move(report.mv).0
+            });
+        self.position_changed();
+        Some(mv)
+    }
+
+    /// Plays the last undone move again
+    pub fn redo(&mut self) -> Option<MoveReport> {
+        if !self.allows_undo() {
+            return None;
+        }
+        let mv = self.undone.pop()?;
+        let (game, report) = self.game.apply_move(mv);
+        self.game = game;
+        self.history.push(report.clone());
+        self.position_changed();
+        Some(report)
+    }
+
+    /// Undoes moves back to a human's turn, so against an AI both the
+    /// AI's reply and the human's move are taken back.
+    /// Without a human player a single move is undone.
+    pub fn undo_turn(&mut self) -> usize {
+        let mut count = 0;
+        while self.undo().is_some() {
+            count += 1;
+            if self.current_player().is_human() || !self.has_human() {
+                break;
+            }
+        }
+        count
+    }
+
+    /// Redoes moves up to the next human turn, the reverse of `undo_turn`
+    pub fn redo_turn(&mut self) -> usize {
+        let mut count = 0;
+        while self.redo().is_some() {
+            count += 1;
+            if self.current_player().is_human() || !self.has_human() || self.game.game_over {
+                break;
+            }
+        }
+        count
+    }
+
+    /// Undoes or redoes moves until `ply` moves have been played, or as near as the
+    /// undone moves allow. As with `undo_turn` a position with the AI to move goes
+    /// back to the human's turn before it. Returns how many moves were undone or redone.
+    pub fn go_to(&mut self, ply: usize) -> usize {
+        let mut count = 0;
+        while self.history.len() > ply && self.undo().is_some() {
+            count += 1;
+        }
+        while self.history.len() < ply && self.redo().is_some() {
+            count += 1;
+        }
+        while self.has_human() && !self.current_player().is_human() && self.undo().is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    /// None until the game is over
+    pub fn summary(&self) -> Option<GameSummary> {
+        let outcome = self.game.outcome()?;
+        let king = self.game.king_tile();
+        let last_captures = self
+            .history
+            .last()
+            .map_or(&[][..], |report| &report.captures);
+        let reason = if self.flag_fell {
+            EndReason::FlagFell
+        } else if outcome == Outcome::DefenderWin {
+            if king.is_some_and(|king| self.game.is_corner(king)) {
+                EndReason::KingEscaped
+            } else {
+                EndReason::NoMoves
+            }
+        } else if king.is_some_and(|king| last_captures.contains(&king)) {
+            EndReason::KingCaptured
+        } else {
+            EndReason::NoMoves
+        };
+
+        let (attackers_captured, defenders_captured) = self.captured();
+        Some(GameSummary {
+            outcome,
+            reason,
+            moves: self.history.len(),
+            attackers_captured,
+            defenders_captured,
+        })
+    }
+
+    /// Pieces taken so far, as the attackers captured and the defenders captured,
+    /// not counting a captured king
+    pub fn captured(&self) -> (usize, usize) {
+        let mut attackers_captured = 0;
+        let mut defenders_captured = 0;
+        // replayed to see which pieces were on the captured tiles
+        let mut game = self.start.clone();
+        for report in &self.history {
+            let taken = report
+                .captures
+                .iter()
+                .filter(|tile| game.board.0[tile.r][tile.c] != PieceType::King)
+                .count();
+            if game.defenders_turn {
+                attackers_captured += taken;
+            } else {
+                defenders_captured += taken;
+            }
+            game = game.apply_move(report.mv).0;
+        }
+        (attackers_captured, defenders_captured)
+    }
+
+    /// The position as an AI playing sees it, from -1 to 1 as in `Player::evaluate`,
+    /// asking the side that just moved first as its search led here. None when
+    /// neither player evaluates positions
+    pub fn evaluation(&self) -> Option<f32> {
+        let (moved, other) = if self.game.defenders_turn {
+            (&self.attacker_player, &self.defender_player)
+        } else {
+            (&self.defender_player, &self.attacker_player)
+        };
+        moved
+            .evaluate(&self.game)
+            .or_else(|| other.evaluate(&self.game))
+    }
+
+    fn allows_undo(&self) -> bool {
+        self.defender_player.allows_undo() && self.attacker_player.allows_undo()
+    }
+
+    fn has_human(&self) -> bool {
+        self.defender_player.is_human() || self.attacker_player.is_human()
+    }
+
+    fn position_changed(&mut self) {
+        // clocks go back to how they were at the start of the turn
+        self.flag_fell = false;
+        if let Some(timer) = &mut self.timer {
+            if let Some(&Some(clock)) = self.clocks.get(self.history.len()) {
+                timer.clock = clock;
+            }
+            timer.restart_turn();
+        }
+        self.defender_player.position_changed(&self.game);
+        self.attacker_player.position_changed(&self.game);
+    }
 }
 
 impl Game {
@@ -166,16 +694,29 @@ impl Game {
         }
     }
 
+    pub fn variant(&self) -> Variant {
+        Variant::Brandubh
+    }
+
+    /// None while the game is still being played
+    pub fn outcome(&self) -> Option<Outcome> {
+        match (self.game_over, self.defender_won) {
+            (false, _) => None,
+            (true, true) => Some(Outcome::DefenderWin),
+            (true, false) => Some(Outcome::AttackerWin),
+        }
+    }
+
     fn piece_type(&self, tile: Tile) -> PieceType {
         self.board.0[tile.r][tile.c]
     }
 
-    fn is_corner(&self, tile: Tile) -> bool {
+    pub fn is_corner(&self, tile: Tile) -> bool {
         let size = self.board.0.len() - 1;
         (tile.r == size || tile.r == 0) && (tile.c == size || tile.c == 0)
     }
 
-    fn throne_tile(&self) -> Tile {
+    pub fn throne_tile(&self) -> Tile {
         let size = self.board.0.len() - 1;
         (size / 2, size / 2).into()
     }
@@ -217,7 +758,9 @@ impl Game {
     }
     /// Checks for captures caused by given move, and if game has ended
     /// updates game state as changes are detected
-    fn check_captures(&mut self, end: Tile) {
+    /// returns the captured tiles, including the king's which stays on the board
+    fn check_captures(&mut self, end: Tile) -> Vec<Tile> {
+        let mut captures = Vec::new();
         let directions = vec![(0, -1), (0, 1), (1, 0), (-1, 0)];
         for dir in directions {
             let neighbor = next_tile(end, dir);
@@ -228,19 +771,28 @@ impl Game {
             {
                 if self.piece_type(neighbor) != PieceType::King {
                     self.board.0[neighbor.r][neighbor.c] = PieceType::Blank;
+                    captures.push(neighbor);
                 } else if self.check_king_capture(neighbor) {
                     self.defender_won = false;
                     self.game_over = true;
-                    return;
+                    captures.push(neighbor);
+                    return captures;
                 }
             }
         }
 
         // TODO: check to see if opponent has no remaining moves
         // rare but could happen
+        captures
     }
 
     pub fn gen_next(&self, src: Tile, dest: Tile) -> Game {
+        self.apply_move(Move { src, dest }).0
+    }
+
+    /// Plays the move, returning the new game and what the move did
+    pub fn apply_move(&self, mv: Move) -> (Game, MoveReport) {
+        let Move { src, dest } = mv;
         let mut game = self.clone();
         game.board.0[dest.r][dest.c] = game.board.0[src.r][src.c];
         game.board.0[src.r][src.c] = PieceType::Blank;
@@ -251,12 +803,12 @@ impl Game {
             game.game_over = true;
         }
 
-        game.check_captures(dest);
+        let captures = game.check_captures(dest);
 
         //change turn
         game.defenders_turn = !game.defenders_turn;
 
-        game
+        (game, MoveReport { mv, captures })
     }
 
     fn friendly_piece(&self, tile: Tile) -> bool {
@@ -326,6 +878,16 @@ impl Game {
             .flat_map(move |dir| self.moves_in_direction(src, *dir).into_iter())
     }
 
+    /// true if the move is one the side to move can make
+    pub fn is_valid_move(&self, mv: Move) -> bool {
+        !self.game_over
+            && self.tile_on_board(mv.src)
+            && self.is_player_piece(mv.src)
+            && self
+                .get_valid_moves(mv.src)
+                .any(|(_, dest)| dest == mv.dest)
+    }
+
     pub fn get_all_valid_moves(&self) -> impl Iterator<Item = (Tile, Tile)> + '_ {
         let size = self.board.0.len();
         (0..size).flat_map(move |r| {
@@ -336,13 +898,54 @@ impl Game {
         })
     }
 
+    /// Pieces the side to move could capture with its next move
+    pub fn threatened_pieces(&self) -> Vec<Tile> {
+        let mut threatened = Vec::new();
+        if self.game_over {
+            return threatened;
+        }
+        for mv in self.get_all_valid_moves() {
+            for tile in self.apply_move(mv.into()).1.captures {
+                if !threatened.contains(&tile) {
+                    threatened.push(tile);
+                }
+            }
+        }
+        threatened
+    }
+
+    /// Ways the king could reach a corner in one or two moves if left alone,
+    /// each the tiles it stops on starting from where it is
+    pub fn king_escape_routes(&self) -> Vec<Vec<Tile>> {
+        let mut routes = Vec::new();
+        let king = match self.king_tile() {
+            Some(king) if !self.game_over => king,
+            _ => return routes,
+        };
+        for (_, dest) in self.get_valid_moves(king) {
+            if self.is_corner(dest) {
+                routes.push(vec![king, dest]);
+                continue;
+            }
+            let mut moved = self.clone();
+            moved.board.0[dest.r][dest.c] = PieceType::King;
+            moved.board.0[king.r][king.c] = PieceType::Blank;
+            for (_, corner) in moved.get_valid_moves(dest) {
+                if self.is_corner(corner) {
+                    routes.push(vec![king, dest, corner]);
+                }
+            }
+        }
+        routes
+    }
+
     pub fn score(&self) -> i32 {
         // defender maximizing
         if self.game_over && self.defender_won {
-            return std::i32::MAX;
+            return i32::MAX;
         }
         if self.game_over && !self.defender_won {
-            return std::i32::MIN;
+            return i32::MIN;
         }
         let mut score = 0;
         let attacker_score = 20;
@@ -393,6 +996,9 @@ impl Game {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::headless::{block_on, ScriptedPlayer};
+    use std::cell::Cell;
+    use std::rc::Rc;
 
     fn new_game(board: Board) -> Game {
         Game {
@@ -415,7 +1021,7 @@ mod test {
         let game = game.gen_next(src, dest);
         assert!(game.game_over);
         assert!(game.defender_won);
-        assert_eq!(game.score(), std::i32::MAX);
+        assert_eq!(game.score(), i32::MAX);
     }
 
     #[test]
@@ -431,4 +1037,164 @@ mod test {
         assert_eq!(new_game.board.0[src.r][src.c], PieceType::Blank);
         assert_ne!(new_game.board.0[dest.r][dest.c], PieceType::Blank);
     }
+
+    fn moves(notation: &[&str]) -> Vec<Move> {
+        notation.iter().map(|mv| mv.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let defender = Box::new(ScriptedPlayer::new(moves(&["c4-c6", "c4-c5"])));
+        let attacker = Box::new(ScriptedPlayer::new(moves(&["a4-a6", "b4-b5"])));
+        let mut state = GameState::new(defender, attacker);
+        for _ in 0..3 {
+            block_on(state.next_turn()).unwrap();
+        }
+        let after_two = Game::new()
+            .gen_next("a4".parse().unwrap(), "a6".parse().unwrap())
+            .gen_next("c4".parse().unwrap(), "c6".parse().unwrap());
+
+        assert_eq!(state.undo(), Some("b4-b5".parse().unwrap()));
+        assert_eq!(state.game.board, after_two.board);
+        assert!(!state.game.defenders_turn);
+        let report = state.redo().unwrap();
+        assert_eq!(report.to_string(), "b4-b5");
+        assert_eq!(state.history().len(), 3);
+        assert!(!state.can_redo());
+
+        // a new move replaces whatever was undone
+        state.undo();
+        state.undo();
+        assert_eq!(state.undone().len(), 2);
+        block_on(state.next_turn()).unwrap();
+        assert!(!state.can_redo());
+        assert_eq!(state.history()[1].to_string(), "c4-c5");
+        while state.undo().is_some() {}
+        assert_eq!(state.game.board, state.start().board);
+    }
+
+    #[test]
+    fn undo_turn_against_ai() {
+        struct Human(ScriptedPlayer);
+        impl Player for Human {
+            fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a> {
+                self.0.choose_move(game)
+            }
+            fn is_human(&self) -> bool {
+                true
+            }
+        }
+
+        let defender = Box::new(ScriptedPlayer::new(moves(&["c4-c6", "e4-e5"])));
+        let attacker = Box::new(Human(ScriptedPlayer::new(moves(&["a4-a6", "b4-b5"]))));
+        let mut state = GameState::new(defender, attacker);
+        for _ in 0..4 {
+            block_on(state.next_turn()).unwrap();
+        }
+        // back to the human's second move
+        assert_eq!(state.undo_turn(), 2);
+        assert_eq!(state.history().len(), 2);
+        assert!(!state.game.defenders_turn);
+        assert_eq!(state.redo_turn(), 2);
+        assert_eq!(state.history().len(), 4);
+        assert_eq!(state.redo_turn(), 0);
+
+        // jumping to the AI's turn stops at the human's before it
+        assert_eq!(state.go_to(3), 2);
+        assert_eq!(state.history().len(), 2);
+        state.go_to(10);
+        assert_eq!(state.history().len(), 4);
+        state.go_to(0);
+        assert_eq!(state.undone().len(), 4);
+    }
+
+    #[test]
+    fn summaries() {
+        let start = Game::from_position_str("3k3/7/1ad4/7/d6/7/3a3 d brandubh").unwrap();
+        // a5 takes b5, then the king escapes
+        let defender = Box::new(ScriptedPlayer::new(moves(&["a3-a5", "d7-g7"])));
+        let attacker = Box::new(ScriptedPlayer::new(moves(&["d1-d2"])));
+        let mut state = GameState::from_game(start, defender, attacker);
+        assert_eq!(state.summary(), None);
+        for _ in 0..3 {
+            block_on(state.next_turn()).unwrap();
+        }
+        assert_eq!(
+            state.summary(),
+            Some(GameSummary {
+                outcome: Outcome::DefenderWin,
+                reason: EndReason::KingEscaped,
+                moves: 3,
+                attackers_captured: 1,
+                defenders_captured: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn captures_under_the_king() {
+        let start = Game::from_position_str("3k3/7/1ad4/7/d6/7/3a3 d brandubh").unwrap();
+        // a5 takes b5, then the king stands where the attacker was taken
+        let defender = Box::new(ScriptedPlayer::new(moves(&["a3-a5", "d7-b7", "b7-b5"])));
+        let attacker = Box::new(ScriptedPlayer::new(moves(&["d1-d2", "d2-d1"])));
+        let mut state = GameState::from_game(start, defender, attacker);
+        for _ in 0..5 {
+            block_on(state.next_turn()).unwrap();
+        }
+        assert_eq!(state.game.king_tile(), Some((1, 2).into()));
+        assert_eq!(state.captured(), (1, 0));
+    }
+
+    #[test]
+    fn flag_fall() {
+        let start = Game::from_position_str("3k3/7/1ad4/7/d6/7/3a3 d brandubh").unwrap();
+        let defender = Box::new(ScriptedPlayer::new(moves(&["a3-a5"])));
+        let attacker = Box::new(ScriptedPlayer::new(moves(&["d1-d2"])));
+        let mut state = GameState::from_game(start, defender, attacker);
+        let now = Rc::new(Cell::new(0.0));
+        let time = Rc::clone(&now);
+        state.set_clock(Clock::new("10+1".parse().unwrap()), move || time.get());
+
+        now.set(4.0);
+        block_on(state.next_turn()).unwrap();
+        assert_eq!(
+            state.clock().unwrap().defender.remaining,
+            Duration::from_secs(7)
+        );
+        // the attackers' move comes too late
+        now.set(20.0);
+        assert_eq!(block_on(state.next_turn()).unwrap(), None);
+        assert_eq!(state.history().len(), 1);
+        assert!(state.clock().unwrap().attacker.flagged());
+        let summary = state.summary().unwrap();
+        assert_eq!(summary.outcome, Outcome::DefenderWin);
+        assert_eq!(summary.reason, EndReason::FlagFell);
+
+        // undoing puts the clocks back as well
+        state.undo_turn();
+        assert!(!state.game.game_over);
+        assert_eq!(
+            state.clock().unwrap().defender.remaining,
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn threats_and_escapes() {
+        let mut game = Game::from_position_str("3k3/7/1ad4/7/d6/7/3a3 d brandubh").unwrap();
+        // a3-a5 takes b5
+        assert_eq!(game.threatened_pieces(), vec![(1, 2).into()]);
+        // d1-d5 through the empty throne takes c5
+        game.defenders_turn = false;
+        assert_eq!(game.threatened_pieces(), vec![(2, 2).into()]);
+
+        let routes = game.king_escape_routes();
+        let direct = routes.iter().filter(|route| route.len() == 2).count();
+        assert_eq!(direct, 2);
+        assert!(routes.contains(&vec![(3, 0).into(), (1, 0).into(), (0, 0).into()]));
+
+        game.game_over = true;
+        assert!(game.threatened_pieces().is_empty());
+        assert!(game.king_escape_routes().is_empty());
+    }
 }
@@ -1,18 +1,719 @@
-use crate::game::{Game, PieceType, Tile};
+use crate::ai::{Difficulty, Evaluator};
+use crate::clock::Clock;
+use crate::game::{
+    EndReason, Game, GameState, GameSummary, Move, MoveReport, Outcome, PieceType, Tile, Variant,
+};
 use macroquad::prelude::*;
+use std::fmt;
+use std::time::Duration;
 
-const BOARD_SIZE: f32 = 600.0;
+// positions and sizes are for the default window, and scaled by `Layout` to fit the actual one
 const SCREEN_EDGE: f32 = 20.0;
-
-const SCREEN_WIDTH: f32 = BOARD_SIZE + 2.0 * SCREEN_EDGE;
 const TOP_BAR_HEIGHT: f32 = 28.0;
-const SCREEN_HEIGHT: f32 = BOARD_SIZE + TOP_BAR_HEIGHT + 2.0 * SCREEN_EDGE;
+const BUTTON_BAR_HEIGHT: f32 = 36.0;
+const BOARD_PIXELS: f32 = 600.0;
+/// top of the board
+const BOARD_TOP: f32 = TOP_BAR_HEIGHT + SCREEN_EDGE;
+
+/// the side panel, right of the board
+const PANEL_X: f32 = BOARD_PIXELS + 2.0 * SCREEN_EDGE;
+const PANEL_WIDTH: f32 = 220.0;
+/// top of the move list in the side panel
+const MOVE_LIST_TOP: f32 = BOARD_TOP + 150.0;
+const MOVE_ROW_HEIGHT: f32 = 22.0;
+/// rows of the move list that fit beside the board
+const MOVE_ROWS: usize = ((BOARD_PIXELS - 150.0) / MOVE_ROW_HEIGHT) as usize;
+
+const SCREEN_WIDTH: f32 = PANEL_X + PANEL_WIDTH + SCREEN_EDGE;
+const SCREEN_HEIGHT: f32 = BOARD_PIXELS + TOP_BAR_HEIGHT + BUTTON_BAR_HEIGHT + 2.0 * SCREEN_EDGE;
+
+/// piece radius as a share of the tile size
+const PIECE_SCALE: f32 = 1.0 / 2.5;
+/// seconds a moved piece takes to slide to its new tile
+const MOVE_TIME: f64 = 0.25;
+/// seconds a captured piece takes to fade away, once the capturing piece arrives
+const CAPTURE_FADE: f64 = 0.5;
+
+const MENU_TOP: f32 = 100.0;
+const MENU_ROW_HEIGHT: f32 = 44.0;
+const MENU_VALUE_X: f32 = 280.0;
+const MENU_VALUE_WIDTH: f32 = 300.0;
+
+const BUTTON_WIDTH: f32 = 70.0;
+const BUTTON_HEIGHT: f32 = 22.0;
+
+/// panel over the board showing the result
+const RESULT_PANEL: Rect = Rect {
+    x: SCREEN_EDGE + 90.0,
+    y: BOARD_TOP + 60.0,
+    w: 415.0,
+    h: 470.0,
+};
+const RESULT_BUTTON_WIDTH: f32 = 200.0;
+const RESULT_BUTTON_HEIGHT: f32 = 36.0;
+
+/// Resizable window starting at the default size, sharp on high-DPI screens
+pub fn window_conf() -> Conf {
+    Conf {
+        window_title: "Tafl".to_string(),
+        window_width: SCREEN_WIDTH as i32,
+        window_height: SCREEN_HEIGHT as i32,
+        window_resizable: true,
+        high_dpi: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    /// sets the text of the page's `aria-live` region, defined in www/index.html
+    fn tafl_announce(text: *const u8, len: usize);
+}
+
+/// Has screen readers read `text` out in the browser, through a live region on
+/// the page. Does nothing on the desktop, where `--announce` prints it instead
+pub fn announce_on_page(text: &str) {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        tafl_announce(text.as_ptr(), text.len());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = text;
+}
+
+/// Where things go on screen, the default window scaled to fit the actual
+/// window and centered, with the board split into `board_size` tiles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// top left of the default window
+    pub origin: Vec2,
+    pub scale: f32,
+    pub board_size: usize,
+}
+
+impl Layout {
+    pub fn new(width: f32, height: f32, board_size: usize) -> Self {
+        let scale = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT);
+        Layout {
+            origin: vec2(
+                (width - SCREEN_WIDTH * scale) / 2.0,
+                (height - SCREEN_HEIGHT * scale) / 2.0,
+            ),
+            scale,
+            board_size,
+        }
+    }
+
+    /// for the window as it is now
+    pub fn current(board_size: usize) -> Self {
+        Layout::new(screen_width(), screen_height(), board_size)
+    }
+
+    /// a point given for the default window
+    pub fn point(&self, x: f32, y: f32) -> Vec2 {
+        self.origin + vec2(x, y) * self.scale
+    }
+
+    /// a rectangle given for the default window
+    pub fn rect(&self, x: f32, y: f32, w: f32, h: f32) -> Rect {
+        let top_left = self.point(x, y);
+        Rect::new(top_left.x, top_left.y, w * self.scale, h * self.scale)
+    }
+
+    pub fn board(&self) -> Rect {
+        self.rect(SCREEN_EDGE, BOARD_TOP, BOARD_PIXELS, BOARD_PIXELS)
+    }
 
-const TILE_SIZE: f32 = 85.0;
-const PIECE_SIZE: f32 = TILE_SIZE / 2.5;
+    pub fn tile_size(&self) -> f32 {
+        BOARD_PIXELS * self.scale / self.board_size as f32
+    }
+
+    pub fn tile_rect(&self, tile: Tile) -> Rect {
+        let board = self.board();
+        let size = self.tile_size();
+        Rect::new(
+            board.x + tile.r as f32 * size,
+            board.y + tile.c as f32 * size,
+            size,
+            size,
+        )
+    }
+
+    pub fn tile_center(&self, tile: Tile) -> Vec2 {
+        self.tile_rect(tile).center()
+    }
+
+    /// the tile under a point, None off the board
+    pub fn tile_at(&self, point: Vec2) -> Option<Tile> {
+        let board = self.board();
+        if !board.contains(point) {
+            return None;
+        }
+        let index = |offset: f32| ((offset / self.tile_size()) as usize).min(self.board_size - 1);
+        Some(Tile {
+            r: index(point.x - board.x),
+            c: index(point.y - board.y),
+        })
+    }
+}
+
+/// Requests from the display other than moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Undo,
+    Redo,
+    Save,
+    Load,
+    /// back to the setup menu
+    NewGame,
+    /// the same players again from the same start
+    Rematch,
+    SwapSides,
+    /// hide the result to look over the game
+    Review,
+    Quit,
+    /// undo or redo to the position after this many moves, from the move list
+    GoTo(usize),
+}
+
+impl Command {
+    const ALL: [Command; 5] = [
+        Command::Undo,
+        Command::Redo,
+        Command::Save,
+        Command::Load,
+        Command::NewGame,
+    ];
+    /// offered on the result panel
+    const GAME_OVER: [Command; 4] = [
+        Command::Rematch,
+        Command::SwapSides,
+        Command::Review,
+        Command::Quit,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Command::Undo => "Undo",
+            Command::Redo => "Redo",
+            Command::Save => "Save",
+            Command::Load => "Load",
+            Command::NewGame => "New",
+            Command::Rematch => "Rematch",
+            Command::SwapSides => "Swap sides",
+            Command::Review => "Review game",
+            Command::Quit => "Quit",
+            Command::GoTo(_) => "Go to",
+        }
+    }
+
+    /// ctrl+z or u to undo, ctrl+y, ctrl+shift+z or r to redo,
+    /// ctrl+s to save, ctrl+o to load and ctrl+n for a new game.
+    /// After the game enter for a rematch, s to swap sides, escape to review and q to quit
+    fn shortcut_pressed(&self) -> bool {
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        match self {
+            Command::Undo => {
+                (ctrl && !shift && is_key_pressed(KeyCode::Z)) || is_key_pressed(KeyCode::U)
+            }
+            Command::Redo => {
+                (ctrl && (is_key_pressed(KeyCode::Y) || (shift && is_key_pressed(KeyCode::Z))))
+                    || is_key_pressed(KeyCode::R)
+            }
+            Command::Save => ctrl && is_key_pressed(KeyCode::S),
+            Command::Load => ctrl && is_key_pressed(KeyCode::O),
+            Command::NewGame => ctrl && is_key_pressed(KeyCode::N),
+            Command::Rematch => is_key_pressed(KeyCode::Enter),
+            Command::SwapSides => !ctrl && is_key_pressed(KeyCode::S),
+            Command::Review => is_key_pressed(KeyCode::Escape),
+            Command::Quit => is_key_pressed(KeyCode::Q),
+            // clicked on the move list instead
+            Command::GoTo(_) => false,
+        }
+    }
+
+    /// in a row under the board, or a column on the result panel
+    fn button(&self, layout: &Layout) -> Rect {
+        if let Some(index) = Command::GAME_OVER
+            .iter()
+            .position(|command| command == self)
+        {
+            return layout.rect(
+                RESULT_PANEL.x + (RESULT_PANEL.w - RESULT_BUTTON_WIDTH) / 2.0,
+                RESULT_PANEL.y + 220.0 + index as f32 * (RESULT_BUTTON_HEIGHT + 14.0),
+                RESULT_BUTTON_WIDTH,
+                RESULT_BUTTON_HEIGHT,
+            );
+        }
+        let index = Command::ALL
+            .iter()
+            .position(|command| command == self)
+            .unwrap();
+        layout.rect(
+            SCREEN_EDGE + index as f32 * (BUTTON_WIDTH + 10.0),
+            BOARD_TOP + BOARD_PIXELS + 8.0,
+            BUTTON_WIDTH,
+            BUTTON_HEIGHT,
+        )
+    }
+
+    fn button_clicked(&self, layout: &Layout) -> bool {
+        is_mouse_button_released(MouseButton::Left)
+            && self.button(layout).contains(mouse_position().into())
+    }
+}
+
+/// AIs offered on the setup menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AIChoice {
+    Minimax,
+    Mcts,
+    Random,
+    Greedy,
+}
+
+impl AIChoice {
+    const ALL: [AIChoice; 4] = [
+        AIChoice::Minimax,
+        AIChoice::Mcts,
+        AIChoice::Random,
+        AIChoice::Greedy,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            AIChoice::Minimax => "Minimax",
+            AIChoice::Mcts => "MCTS",
+            AIChoice::Random => "Random",
+            AIChoice::Greedy => "Greedy",
+        }
+    }
+
+    fn has_strength(&self) -> bool {
+        matches!(self, AIChoice::Minimax | AIChoice::Mcts)
+    }
+}
+
+/// Colours for everything drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub background: Color,
+    pub board: Color,
+    pub grid: Color,
+    /// the throne and corners
+    pub special: Color,
+    /// coordinates on the board
+    pub label: Color,
+    pub attacker: Color,
+    pub defender: Color,
+    pub king: Color,
+    pub piece_edge: Color,
+    pub crown: Color,
+    pub highlight: Color,
+    pub text: Color,
+    /// fill for the tiles of the last move
+    pub last_move: Color,
+    /// ring around pieces that can be taken
+    pub threat: Color,
+    /// lines along the king's ways out
+    pub escape: Color,
+}
+
+/// Colour schemes that can be picked on the setup menu
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Classic,
+    Wood,
+    /// black and white with bright markings, and pieces told apart by more than colour
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Classic, Theme::Wood, Theme::HighContrast];
+
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Classic => Palette {
+                background: BLACK,
+                board: Color::new(0.06, 0.08, 0.16, 1.0),
+                grid: BLUE,
+                special: Color::new(0.16, 0.22, 0.45, 1.0),
+                label: GRAY,
+                attacker: RED,
+                defender: BLUE,
+                king: WHITE,
+                piece_edge: BLACK,
+                crown: GOLD,
+                highlight: GREEN,
+                text: WHITE,
+                last_move: Color::new(1.0, 1.0, 0.0, 0.2),
+                threat: ORANGE,
+                escape: Color::new(1.0, 0.84, 0.0, 0.5),
+            },
+            Theme::Wood => Palette {
+                background: Color::new(0.2, 0.12, 0.06, 1.0),
+                board: Color::new(0.87, 0.72, 0.53, 1.0),
+                grid: Color::new(0.5, 0.33, 0.17, 1.0),
+                special: Color::new(0.65, 0.45, 0.25, 1.0),
+                label: Color::new(0.4, 0.25, 0.1, 1.0),
+                attacker: Color::new(0.18, 0.12, 0.08, 1.0),
+                defender: Color::new(0.96, 0.92, 0.82, 1.0),
+                king: Color::new(0.96, 0.92, 0.82, 1.0),
+                piece_edge: Color::new(0.1, 0.06, 0.03, 1.0),
+                crown: GOLD,
+                highlight: DARKGREEN,
+                text: WHITE,
+                last_move: Color::new(0.95, 0.85, 0.2, 0.4),
+                threat: Color::new(0.8, 0.1, 0.1, 1.0),
+                escape: Color::new(0.1, 0.45, 0.1, 0.6),
+            },
+            Theme::HighContrast => Palette {
+                background: BLACK,
+                board: BLACK,
+                grid: WHITE,
+                special: Color::new(0.45, 0.45, 0.0, 1.0),
+                label: YELLOW,
+                attacker: Color::new(1.0, 0.45, 0.0, 1.0),
+                defender: WHITE,
+                king: WHITE,
+                piece_edge: WHITE,
+                crown: YELLOW,
+                highlight: Color::new(0.0, 1.0, 1.0, 1.0),
+                text: WHITE,
+                last_move: Color::new(0.0, 1.0, 1.0, 0.35),
+                threat: MAGENTA,
+                escape: Color::new(0.0, 1.0, 0.0, 0.8),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Theme::Classic => "classic",
+            Theme::Wood => "wood",
+            Theme::HighContrast => "high contrast",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Extra markings on the board, each turned on and off with its number key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    LastMove,
+    /// taken pieces fading out
+    Captures,
+    /// pieces that can be taken next move, by either side
+    Threats,
+    /// the king's ways to a corner in one or two moves
+    KingPaths,
+}
+
+impl Overlay {
+    pub const ALL: [Overlay; 4] = [
+        Overlay::LastMove,
+        Overlay::Captures,
+        Overlay::Threats,
+        Overlay::KingPaths,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Overlay::LastMove => "last move",
+            Overlay::Captures => "captures",
+            Overlay::Threats => "threats",
+            Overlay::KingPaths => "king paths",
+        }
+    }
+
+    fn key(&self) -> KeyCode {
+        match self {
+            Overlay::LastMove => KeyCode::Key1,
+            Overlay::Captures => KeyCode::Key2,
+            Overlay::Threats => KeyCode::Key3,
+            Overlay::KingPaths => KeyCode::Key4,
+        }
+    }
+}
+
+/// share of an animation done after `elapsed` of `duration` seconds, slowing towards the end
+fn progress(elapsed: f64, duration: f64) -> f32 {
+    let done = (elapsed / duration).clamp(0.0, 1.0) as f32;
+    1.0 - (1.0 - done) * (1.0 - done)
+}
+
+/// The piece of the last move on its way to the destination
+struct Sliding {
+    mv: Move,
+    piece: PieceType,
+    /// from `get_time`
+    start: f64,
+}
+
+impl Sliding {
+    fn center(&self, layout: &Layout, now: f64) -> Vec2 {
+        let from = layout.tile_center(self.mv.src);
+        let to = layout.tile_center(self.mv.dest);
+        from.lerp(to, progress(now - self.start, MOVE_TIME))
+    }
+}
+
+/// What the side panel shows of the game, from `Display::show_state`
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Panel {
+    /// played and then undone moves, in notation
+    moves: Vec<String>,
+    /// how many of the moves are played, the rest are undone
+    ply: usize,
+    /// numbering starts with `1...` when the defenders moved first
+    defenders_first: bool,
+    attackers_captured: usize,
+    defenders_captured: usize,
+    /// from -1 for the attackers winning to 1 for the defenders
+    balance: f32,
+    /// None for an untimed game
+    clock: Option<Clock>,
+    /// when the clock was shown, to keep it counting down between updates
+    clock_time: f64,
+}
+
+impl Panel {
+    /// The clickable entries of the move list that fit, the start and then
+    /// each move, with the number of moves played at each
+    fn cells(&self, layout: &Layout) -> Vec<(usize, Rect)> {
+        let offset = self.defenders_first as usize;
+        let row_of = |ply: usize| {
+            if ply == 0 {
+                0
+            } else {
+                (ply - 1 + offset) / 2 + 1
+            }
+        };
+        // scrolled to keep the current position in view
+        let first_row = row_of(self.ply).saturating_sub(MOVE_ROWS - 1);
+        (0..=self.moves.len())
+            .filter(|&ply| (first_row..first_row + MOVE_ROWS).contains(&row_of(ply)))
+            .map(|ply| {
+                let y = MOVE_LIST_TOP + (row_of(ply) - first_row) as f32 * MOVE_ROW_HEIGHT;
+                let x = match ply {
+                    0 => PANEL_X,
+                    _ => PANEL_X + 36.0 + ((ply - 1 + offset) % 2) as f32 * 88.0,
+                };
+                (ply, layout.rect(x, y, 84.0, MOVE_ROW_HEIGHT))
+            })
+            .collect()
+    }
+}
+
+/// A captured piece still being drawn as it shrinks and fades
+struct Fading {
+    tile: Tile,
+    piece: PieceType,
+    /// from `get_time`, can be in the future while the capturing piece slides in
+    start: f64,
+}
+
+/// What the player did on the board while it waited for a move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoardInput {
+    /// mouse button or finger down on a tile
+    Press(Tile),
+    /// let go, over a tile or off the board
+    Release(Option<Tile>),
+    /// right click, escape or a cancelled touch
+    Cancel,
+    /// enter or space on the keyboard cursor's tile, like a click
+    Choose(Tile),
+    /// a move typed in notation, not yet checked to be legal
+    Typed(Move),
+}
+
+/// Who plays one side, as picked on the setup menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SideSetup {
+    pub human: bool,
+    pub ai: AIChoice,
+    /// minimax presets, or more simulations for mcts
+    pub strength: Difficulty,
+}
+
+impl SideSetup {
+    /// player spec, as used on the command line
+    pub fn spec(&self) -> String {
+        if self.human {
+            return "human".to_string();
+        }
+        let level = Difficulty::ALL
+            .iter()
+            .position(|&difficulty| difficulty == self.strength)
+            .unwrap();
+        match self.ai {
+            AIChoice::Minimax => self.strength.to_string(),
+            AIChoice::Mcts => format!("mcts:{}", 50 << level),
+            AIChoice::Random => "random".to_string(),
+            AIChoice::Greedy => "greedy".to_string(),
+        }
+    }
+}
+
+/// Choices on the setup menu shown before a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Setup {
+    pub attacker: SideSetup,
+    pub defender: SideSetup,
+    pub variant: Variant,
+    pub theme: Theme,
+}
+
+impl Default for Setup {
+    /// playing the attackers against the hard AI
+    fn default() -> Self {
+        let ai = SideSetup {
+            human: false,
+            ai: AIChoice::Minimax,
+            strength: Difficulty::Hard,
+        };
+        Setup {
+            attacker: SideSetup { human: true, ..ai },
+            defender: ai,
+            variant: Variant::Brandubh,
+            theme: Theme::default(),
+        }
+    }
+}
+
+/// A line of the setup menu, sides given by whether they defend
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SetupRow {
+    Player(bool),
+    AI(bool),
+    Strength(bool),
+    Variant,
+    Theme,
+}
+
+/// moves `step` places through `options` from `current`, wrapping around
+fn cycle<T: PartialEq + Copy>(options: &[T], current: T, step: isize) -> T {
+    let index = options
+        .iter()
+        .position(|&option| option == current)
+        .unwrap();
+    let count = options.len() as isize;
+    options[(index as isize + step).rem_euclid(count) as usize]
+}
+
+impl Setup {
+    /// the rows shown, AI settings only for AI sides
+    fn rows(&self) -> Vec<SetupRow> {
+        let mut rows = Vec::new();
+        for defender in [false, true] {
+            let side = self.side(defender);
+            rows.push(SetupRow::Player(defender));
+            if !side.human {
+                rows.push(SetupRow::AI(defender));
+                if side.ai.has_strength() {
+                    rows.push(SetupRow::Strength(defender));
+                }
+            }
+        }
+        rows.push(SetupRow::Variant);
+        rows.push(SetupRow::Theme);
+        rows
+    }
+
+    fn side(&self, defender: bool) -> &SideSetup {
+        if defender {
+            &self.defender
+        } else {
+            &self.attacker
+        }
+    }
+
+    fn side_mut(&mut self, defender: bool) -> &mut SideSetup {
+        if defender {
+            &mut self.defender
+        } else {
+            &mut self.attacker
+        }
+    }
+
+    fn text(&self, row: SetupRow) -> (String, String) {
+        let side_name = |defender| if defender { "Defender" } else { "Attacker" };
+        match row {
+            SetupRow::Player(defender) => (
+                side_name(defender).to_string(),
+                if self.side(defender).human {
+                    "Human"
+                } else {
+                    "AI"
+                }
+                .to_string(),
+            ),
+            SetupRow::AI(defender) => (
+                format!("{} AI", side_name(defender)),
+                self.side(defender).ai.name().to_string(),
+            ),
+            SetupRow::Strength(defender) => (
+                format!("{} strength", side_name(defender)),
+                self.side(defender).strength.to_string(),
+            ),
+            SetupRow::Variant => ("Variant".to_string(), self.variant.to_string()),
+            SetupRow::Theme => ("Theme".to_string(), self.theme.to_string()),
+        }
+    }
+
+    fn change(&mut self, row: SetupRow, step: isize) {
+        match row {
+            SetupRow::Player(defender) => {
+                let side = self.side_mut(defender);
+                side.human = !side.human;
+            }
+            SetupRow::AI(defender) => {
+                let side = self.side_mut(defender);
+                side.ai = cycle(&AIChoice::ALL, side.ai, step);
+            }
+            SetupRow::Strength(defender) => {
+                let side = self.side_mut(defender);
+                side.strength = cycle(&Difficulty::ALL, side.strength, step);
+            }
+            SetupRow::Variant => self.variant = cycle(&Variant::ALL, self.variant, step),
+            SetupRow::Theme => self.theme = cycle(&Theme::ALL, self.theme, step),
+        }
+    }
+}
 
 pub struct Display {
     pub current_selection: Option<Tile>,
+    /// the piece on this tile follows the pointer instead
+    pub dragging: Option<Tile>,
+    /// moved with the arrow keys, hidden until one is pressed
+    pub cursor: Option<Tile>,
+    /// a move being typed
+    pub typed: String,
+    /// shown under the board, like an announced move or a mistyped one
+    pub message: Option<String>,
+    /// set when input other than a move interrupts waiting for a click
+    pub command: Option<Command>,
+    /// shown over the board once the game is over, None while reviewing
+    pub result: Option<GameSummary>,
+    pub theme: Theme,
+    /// marked on the board, set from the game's history
+    pub last_move: Option<MoveReport>,
+    /// the overlays turned on
+    pub overlays: Vec<Overlay>,
+    sliding: Option<Sliding>,
+    fading: Vec<Fading>,
+    /// typed since the last frame, in order
+    chars: Vec<char>,
+    panel: Panel,
+    /// the side to move's pieces the opponent could take, from `show_state`
+    threatened: Vec<Tile>,
+    /// from `show_state` so they aren't worked out every frame
+    escape_routes: Vec<Vec<Tile>>,
 }
 impl Default for Display {
     fn default() -> Self {
@@ -24,41 +725,354 @@ impl Display {
     pub fn new() -> Self {
         Display {
             current_selection: None,
+            dragging: None,
+            cursor: None,
+            typed: String::new(),
+            message: None,
+            command: None,
+            result: None,
+            theme: Theme::default(),
+            last_move: None,
+            overlays: Overlay::ALL.to_vec(),
+            sliding: None,
+            fading: Vec::new(),
+            chars: Vec::new(),
+            panel: Panel::default(),
+            threatened: Vec::new(),
+            escape_routes: Vec::new(),
+        }
+    }
+
+    pub fn shows(&self, overlay: Overlay) -> bool {
+        self.overlays.contains(&overlay)
+    }
+
+    pub fn toggle(&mut self, overlay: Overlay) {
+        if self.shows(overlay) {
+            self.overlays.retain(|&shown| shown != overlay);
+        } else {
+            self.overlays.push(overlay);
+        }
+    }
+
+    /// Starts animating the move in `report`, played in `before`: the piece slides
+    /// over and then the pieces it took fade out. Drawing carries on as normal meanwhile.
+    pub fn show_move(&mut self, before: &Game, report: &MoveReport) {
+        let now = get_time();
+        let src = report.mv.src;
+        self.sliding = Some(Sliding {
+            mv: report.mv,
+            piece: before.board.0[src.r][src.c],
+            start: now,
+        });
+        for &tile in &report.captures {
+            let piece = before.board.0[tile.r][tile.c];
+            // a captured king stays on the board
+            if piece != PieceType::King {
+                self.fading.push(Fading {
+                    tile,
+                    piece,
+                    start: now + MOVE_TIME,
+                });
+            }
         }
     }
 
-    pub async fn draw_game(&self, game: &Game) {
-        clear_background(BLACK);
-        request_new_screen_size(SCREEN_WIDTH, SCREEN_HEIGHT);
-        draw_board(game);
-        draw_pieces(game);
+    /// Takes the last move and what the side panel shows from the game
+    pub fn show_state(&mut self, state: &GameState) {
+        let size = state.game.board_size();
+        let history = state.history();
+        self.last_move = history.last().cloned();
+        let played = history.iter().map(|report| report.to_notation(size));
+        let undone = state.undone().iter().rev().map(|mv| mv.to_notation(size));
+        let (attackers_captured, defenders_captured) = state.captured();
+        self.panel = Panel {
+            moves: played.chain(undone).collect(),
+            ply: history.len(),
+            defenders_first: state.start().defenders_turn,
+            attackers_captured,
+            defenders_captured,
+            // as the AI sees it, or the hand written evaluation between people
+            balance: state
+                .evaluation()
+                .unwrap_or_else(|| Evaluator::default().balance(&state.game)),
+            clock: state.clock(),
+            clock_time: get_time(),
+        };
+
+        // what the opponent could take if the side to move leaves it
+        let mut opponent = state.game.clone();
+        opponent.defenders_turn = !opponent.defenders_turn;
+        self.threatened = opponent.threatened_pieces();
+        self.escape_routes = state.game.king_escape_routes();
+    }
+
+    /// whether a move is still being shown
+    pub fn animating(&self) -> bool {
+        let now = get_time();
+        self.sliding.is_some()
+            || self
+                .fading
+                .iter()
+                .any(|fading| now < fading.start + CAPTURE_FADE)
+    }
+
+    /// for when the position changes other than by a move, like an undo
+    pub fn stop_animations(&mut self) {
+        self.sliding = None;
+        self.fading.clear();
+    }
+
+    pub async fn draw_game(&mut self, game: &Game) {
+        // the number keys are part of a move while one is typed
+        for overlay in Overlay::ALL {
+            if self.typed.is_empty() && is_key_pressed(overlay.key()) {
+                self.toggle(overlay);
+            }
+        }
+        let now = get_time();
+        self.fading
+            .retain(|fading| now - fading.start < CAPTURE_FADE);
+        // finished, or the position moved on some other way
+        if let Some(sliding) = &self.sliding {
+            let dest = sliding.mv.dest;
+            if now - sliding.start >= MOVE_TIME || game.board.0[dest.r][dest.c] != sliding.piece {
+                self.sliding = None;
+            }
+        }
+
+        let layout = Layout::current(game.board_size());
+        let palette = self.theme.palette();
+        clear_background(palette.background);
+        draw_board(game, &layout, &palette);
+        match &self.last_move {
+            Some(report) if self.shows(Overlay::LastMove) => {
+                for tile in [report.mv.src, report.mv.dest] {
+                    let rect = layout.tile_rect(tile);
+                    draw_rectangle(rect.x, rect.y, rect.w, rect.h, palette.last_move);
+                }
+            }
+            _ => (),
+        }
+        if self.shows(Overlay::KingPaths) {
+            draw_escape_routes(&self.escape_routes, &layout, &palette);
+        }
+        let radius = layout.tile_size() * PIECE_SCALE;
+        if self.shows(Overlay::Captures) {
+            for fading in &self.fading {
+                let left = 1.0 - progress(now - fading.start, CAPTURE_FADE);
+                let center = layout.tile_center(fading.tile);
+                let shrunk = radius * (0.5 + 0.5 * left);
+                draw_piece(fading.piece, center, shrunk, &palette, left);
+            }
+        }
+        let moving = self.sliding.as_ref().map(|sliding| sliding.mv.dest);
+        let hidden = moving
+            .into_iter()
+            .chain(self.dragging)
+            .collect::<Vec<Tile>>();
+        draw_pieces(game, &layout, &palette, &hidden);
+        if let Some(sliding) = &self.sliding {
+            let center = sliding.center(&layout, now);
+            draw_piece(sliding.piece, center, radius, &palette, 1.0);
+        }
+        if self.shows(Overlay::Threats) {
+            draw_threats(&self.threatened, &layout, &palette);
+        }
 
         //Highlight moves for selected tile
         if let Some(tile) = self.current_selection {
             for (_, dest) in game.get_valid_moves(tile) {
-                highlight_tile(dest);
+                highlight_tile(dest, &layout, &palette);
             }
         }
 
+        if let Some(tile) = self.cursor {
+            let rect = layout.tile_rect(tile);
+            draw_rectangle_lines(
+                rect.x,
+                rect.y,
+                rect.w,
+                rect.h,
+                6.0 * layout.scale,
+                palette.highlight,
+            );
+        }
+
+        // held a little larger, over everything on the board
+        if let Some(tile) = self.dragging {
+            let piece = game.board.0[tile.r][tile.c];
+            let at = Vec2::from(mouse_position());
+            draw_piece(piece, at, radius * 1.1, &palette, 1.0);
+        }
+
+        draw_buttons(&Command::ALL, &layout, &palette);
+        let status = if self.typed.is_empty() {
+            self.message.clone()
+        } else {
+            Some(format!("move: {}_", self.typed))
+        };
+        if let Some(status) = status {
+            let at = layout.point(
+                SCREEN_EDGE + Command::ALL.len() as f32 * (BUTTON_WIDTH + 10.0),
+                BOARD_TOP + BOARD_PIXELS + 24.0,
+            );
+            draw_text(&status, at.x, at.y, 16.0 * layout.scale, palette.text);
+        }
+        if let Some(summary) = &self.result {
+            draw_result(summary, &layout, &palette);
+        }
+
         // show fps
-        draw_text(format!("FPS: {}", get_fps()).as_str(), 0., 16., 32., WHITE);
+        let size = 32.0 * layout.scale;
+        draw_text(
+            &format!("FPS: {}", get_fps()),
+            0.0,
+            size / 2.0,
+            size,
+            palette.text,
+        );
+        self.draw_overlay_keys(&layout, &palette);
+        self.draw_panel(game, &layout, &palette);
 
         next_frame().await;
+        // for the frame just started, as macroquad 0.4.4 (pinned in Cargo.toml) hands them out
+        // newest first and keeps them until asked
+        self.chars = std::iter::from_fn(get_char_pressed).collect();
+        self.chars.reverse();
     }
 
-    pub async fn next_tile_click(&self, game: &Game) -> Tile {
+    /// Waits for the next press, release or cancel on the board.
+    /// None when a command was given instead, which is left in `command`.
+    pub async fn next_board_input(&mut self, game: &Game) -> Option<BoardInput> {
         loop {
             // draw game need to happen first
             // as mouse pressed is frame dependent
             self.draw_game(game).await;
 
+            let layout = Layout::current(game.board_size());
+            if let Some(command) = self.pressed_command(&layout) {
+                self.command = Some(command);
+                return None;
+            }
+            if let Some(input) = self.keyboard_input(game) {
+                return Some(input);
+            }
+            // touches are also seen as the left mouse button, apart from being cancelled
+            let touch_cancelled = touches()
+                .iter()
+                .any(|touch| touch.phase == TouchPhase::Cancelled);
+            if is_mouse_button_pressed(MouseButton::Right) || touch_cancelled {
+                return Some(BoardInput::Cancel);
+            }
+            let tile = mouse_tile_position(&layout);
+            if is_mouse_button_pressed(MouseButton::Left) {
+                if let Some(tile) = tile {
+                    return Some(BoardInput::Press(tile));
+                }
+            }
             if is_mouse_button_released(MouseButton::Left) {
-                if let Some(tile) = mouse_tile_position() {
-                    if game.tile_on_board(tile) {
-                        return tile;
-                    }
+                return Some(BoardInput::Release(tile));
+            }
+        }
+    }
+
+    /// Shows the setup menu until the game is started with the start button or enter.
+    /// Rows are changed by clicking them or with the arrow keys.
+    pub async fn setup_menu(&mut self, setup: &mut Setup) {
+        let mut selected = 0;
+        loop {
+            let layout = Layout::current(crate::game::BOARD_SIZE);
+            let text_size = |size: f32| size * layout.scale;
+            // previewing the theme being picked
+            self.theme = setup.theme;
+            let palette = self.theme.palette();
+            let rows = setup.rows();
+            selected = selected.min(rows.len() - 1);
+            clear_background(palette.background);
+            let title = layout.point(SCREEN_EDGE, 60.0);
+            draw_text("Tafl", title.x, title.y, text_size(56.0), palette.text);
+
+            let mouse = Vec2::from(mouse_position());
+            let clicked = is_mouse_button_released(MouseButton::Left);
+            let mut change = None;
+            for (index, &row) in rows.iter().enumerate() {
+                let y = MENU_TOP + index as f32 * MENU_ROW_HEIGHT;
+                let (label, value) = setup.text(row);
+                let color = if index == selected {
+                    palette.highlight
+                } else {
+                    palette.text
+                };
+                let label_at = layout.point(SCREEN_EDGE, y + 24.0);
+                draw_text(&label, label_at.x, label_at.y, text_size(28.0), color);
+                let value_box = layout.rect(MENU_VALUE_X, y, MENU_VALUE_WIDTH, 32.0);
+                draw_rectangle_lines(
+                    value_box.x,
+                    value_box.y,
+                    value_box.w,
+                    value_box.h,
+                    2.0,
+                    GRAY,
+                );
+                let value_at = layout.point(MENU_VALUE_X + 10.0, y + 24.0);
+                draw_text(
+                    &format!("< {value} >"),
+                    value_at.x,
+                    value_at.y,
+                    text_size(28.0),
+                    color,
+                );
+
+                if clicked && value_box.contains(mouse) {
+                    selected = index;
+                    // the left half goes back
+                    let step = if mouse.x < value_box.center().x {
+                        -1
+                    } else {
+                        1
+                    };
+                    change = Some((row, step));
                 }
             }
+
+            let start_y = MENU_TOP + rows.len() as f32 * MENU_ROW_HEIGHT + 20.0;
+            let start = layout.rect(SCREEN_EDGE, start_y, MENU_VALUE_WIDTH, 40.0);
+            draw_rectangle_lines(start.x, start.y, start.w, start.h, 2.0, GREEN);
+            let start_at = layout.point(SCREEN_EDGE + 12.0, start_y + 29.0);
+            draw_text("Start", start_at.x, start_at.y, text_size(32.0), GREEN);
+            let start_clicked = clicked && start.contains(mouse);
+            if is_key_pressed(KeyCode::Up) {
+                selected = (selected + rows.len() - 1) % rows.len();
+            }
+            if is_key_pressed(KeyCode::Down) {
+                selected = (selected + 1) % rows.len();
+            }
+            if is_key_pressed(KeyCode::Left) {
+                change = Some((rows[selected], -1));
+            }
+            if is_key_pressed(KeyCode::Right) {
+                change = Some((rows[selected], 1));
+            }
+            if let Some((row, step)) = change {
+                setup.change(row, step);
+            }
+            self.theme = setup.theme;
+
+            next_frame().await;
+            if start_clicked || is_key_pressed(KeyCode::Enter) {
+                return;
+            }
+        }
+    }
+
+    /// Shows the game until a command is given, for when no one is moving
+    pub async fn next_command(&mut self, game: &Game) -> Command {
+        loop {
+            self.draw_game(game).await;
+            if let Some(command) = self.pressed_command(&Layout::current(game.board_size())) {
+                return command;
+            }
         }
     }
 }
@@ -67,63 +1081,565 @@ impl Display {
 // Helper functions for Display
 //
 
-fn highlight_tile(tile: Tile) {
-    let x = tile_position(tile.r) + 2.0;
-    let y = tile_position(tile.c) + 2.0;
-    draw_rectangle_lines(x, y, TILE_SIZE - 4.0, TILE_SIZE - 4.0, 2.0, GREEN)
+impl Display {
+    /// Arrow keys move the cursor and enter or space choose its tile,
+    /// or a move can be typed and entered. Escape clears typing, or cancels.
+    fn keyboard_input(&mut self, game: &Game) -> Option<BoardInput> {
+        let size = game.board_size();
+        for typed in std::mem::take(&mut self.chars) {
+            let typed = typed.to_ascii_lowercase();
+            // only a file letter starts a move, so other keys stay shortcuts
+            let starts_move = typed.is_ascii_lowercase() && ((typed as u8 - b'a') as usize) < size;
+            let continues_move = typed.is_ascii_alphanumeric() || typed == '-';
+            if starts_move || (!self.typed.is_empty() && continues_move) {
+                self.typed.push(typed);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.typed.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            if self.typed.is_empty() {
+                return Some(BoardInput::Cancel);
+            }
+            self.typed.clear();
+        }
+
+        let steps = [
+            (KeyCode::Left, (-1, 0)),
+            (KeyCode::Right, (1, 0)),
+            (KeyCode::Up, (0, -1)),
+            (KeyCode::Down, (0, 1)),
+        ];
+        for (key, (dr, dc)) in steps {
+            if is_key_pressed(key) {
+                // appearing where the player is looking
+                let from = self
+                    .cursor
+                    .or(self.current_selection)
+                    .unwrap_or(game.throne_tile());
+                let step =
+                    |at: usize, by: isize| (at as isize + by).clamp(0, size as isize - 1) as usize;
+                self.cursor = Some(match self.cursor {
+                    Some(_) => Tile {
+                        r: step(from.r, dr),
+                        c: step(from.c, dc),
+                    },
+                    None => from,
+                });
+            }
+        }
+
+        let enter = is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter);
+        if enter && !self.typed.is_empty() {
+            let typed = std::mem::take(&mut self.typed);
+            match Move::from_notation(&typed, size) {
+                Ok(mv) => return Some(BoardInput::Typed(mv)),
+                Err(error) => self.message = Some(error.to_string()),
+            }
+        } else if enter || is_key_pressed(KeyCode::Space) {
+            return self.cursor.map(BoardInput::Choose);
+        }
+        None
+    }
+
+    /// the overlays' keys along the top, dimmed when turned off
+    fn draw_overlay_keys(&self, layout: &Layout, palette: &Palette) {
+        let size = 18.0 * layout.scale;
+        let mut at = layout.point(200.0, 20.0);
+        for (index, overlay) in Overlay::ALL.iter().enumerate() {
+            let text = format!("{} {}", index + 1, overlay.name());
+            let color = if self.shows(*overlay) {
+                palette.text
+            } else {
+                palette.label
+            };
+            draw_text(&text, at.x, at.y, size, color);
+            at.x += measure_text(&text, None, size as u16, 1.0).width + 14.0 * layout.scale;
+        }
+    }
+
+    fn draw_panel(&self, game: &Game, layout: &Layout, palette: &Palette) {
+        let panel = &self.panel;
+        let text = |text: &str, x: f32, y: f32, size: f32, color: Color| {
+            let at = layout.point(x, y);
+            draw_text(text, at.x, at.y, size * layout.scale, color);
+        };
+
+        let (turn, color) = match (game.game_over, game.defenders_turn) {
+            (true, _) => ("Game over", palette.text),
+            (false, true) => ("Defenders to move", palette.defender),
+            (false, false) => ("Attackers to move", palette.attacker),
+        };
+        text(turn, PANEL_X, BOARD_TOP + 20.0, 24.0, color);
+        let taken = [
+            ("Attackers taken", panel.attackers_captured),
+            ("Defenders taken", panel.defenders_captured),
+        ];
+        for (index, (label, count)) in taken.iter().enumerate() {
+            let y = BOARD_TOP + 50.0 + index as f32 * 22.0;
+            text(&format!("{label}: {count}"), PANEL_X, y, 18.0, palette.text);
+        }
+        if let Some(mut clock) = panel.clock {
+            let running = !game.game_over;
+            if running {
+                let elapsed = (get_time() - panel.clock_time).max(0.0);
+                clock.spend(game.defenders_turn, Duration::from_secs_f64(elapsed));
+            }
+            // each side's time beside its count, lit up while it runs
+            for (index, defender) in [false, true].into_iter().enumerate() {
+                let side = clock.side(defender);
+                let color = if side.flagged() {
+                    palette.threat
+                } else if running && defender == game.defenders_turn {
+                    palette.highlight
+                } else {
+                    palette.text
+                };
+                let time = side.to_string();
+                let size = 20.0 * layout.scale;
+                let width = measure_text(&time, None, size as u16, 1.0).width;
+                let y = BOARD_TOP + 50.0 + index as f32 * 22.0;
+                let at = layout.point(PANEL_X + PANEL_WIDTH, y);
+                draw_text(&time, at.x - width, at.y, size, color);
+            }
+        }
+
+        // the defenders' share from the right, the attackers' from the left
+        let bar = layout.rect(PANEL_X, BOARD_TOP + 90.0, PANEL_WIDTH, 18.0);
+        let defenders = bar.w * (panel.balance + 1.0) / 2.0;
+        draw_rectangle(bar.x, bar.y, bar.w - defenders, bar.h, palette.attacker);
+        draw_rectangle(
+            bar.right() - defenders,
+            bar.y,
+            defenders,
+            bar.h,
+            palette.defender,
+        );
+        draw_rectangle_lines(bar.x, bar.y, bar.w, bar.h, 2.0, palette.grid);
+        let middle = bar.center().x;
+        draw_line(middle, bar.y, middle, bar.bottom(), 2.0, palette.text);
+
+        text("Moves", PANEL_X, MOVE_LIST_TOP - 8.0, 20.0, palette.text);
+        let offset = panel.defenders_first as usize;
+        for (ply, cell) in panel.cells(layout) {
+            let label = match ply {
+                0 => "start".to_string(),
+                _ => panel.moves[ply - 1].clone(),
+            };
+            let color = if ply == panel.ply {
+                palette.highlight
+            } else if ply > panel.ply {
+                palette.label
+            } else {
+                palette.text
+            };
+            let size = 18.0 * layout.scale;
+            draw_text(&label, cell.x, cell.y + size, size, color);
+            // move numbers before each row's first column
+            let slot = ply + offset;
+            if ply > 0 && (slot % 2 == 1 || ply == 1) {
+                let number = match slot % 2 {
+                    1 => format!("{}.", slot / 2 + 1),
+                    _ => format!("{}...", slot / 2),
+                };
+                let x = layout.point(PANEL_X, 0.0).x;
+                draw_text(&number, x, cell.y + size, size, palette.label);
+            }
+        }
+    }
+
+    fn pressed_command(&self, layout: &Layout) -> Option<Command> {
+        // the result panel's buttons only while it's shown
+        let game_over = match self.result {
+            Some(_) => &Command::GAME_OVER[..],
+            None => &[],
+        };
+        let pressed = Command::ALL
+            .iter()
+            .chain(game_over)
+            .copied()
+            .find(|command| command.shortcut_pressed() || command.button_clicked(layout));
+        if pressed.is_some() || !is_mouse_button_released(MouseButton::Left) {
+            return pressed;
+        }
+        let mouse = Vec2::from(mouse_position());
+        self.panel
+            .cells(layout)
+            .into_iter()
+            .find(|(_, cell)| cell.contains(mouse))
+            .map(|(ply, _)| Command::GoTo(ply))
+    }
 }
 
-/// maps tile index to pixel position
-fn tile_position(i: usize) -> f32 {
-    TILE_SIZE * (i as f32) + SCREEN_EDGE
+fn draw_buttons(commands: &[Command], layout: &Layout, palette: &Palette) {
+    for command in commands {
+        let button = command.button(layout);
+        draw_rectangle_lines(button.x, button.y, button.w, button.h, 2.0, palette.grid);
+        let size = button.h * 0.75;
+        draw_text(
+            command.label(),
+            button.x + 8.0 * layout.scale,
+            button.y + size,
+            size,
+            palette.text,
+        );
+    }
 }
 
-fn tile_index_from_mouse(i: f32) -> usize {
-    ((i - SCREEN_EDGE - TILE_SIZE / 2.0) / TILE_SIZE).round() as usize
+fn draw_result(summary: &GameSummary, layout: &Layout, palette: &Palette) {
+    let panel = layout.rect(
+        RESULT_PANEL.x,
+        RESULT_PANEL.y,
+        RESULT_PANEL.w,
+        RESULT_PANEL.h,
+    );
+    draw_rectangle(
+        panel.x,
+        panel.y,
+        panel.w,
+        panel.h,
+        Color {
+            a: 0.85,
+            ..palette.background
+        },
+    );
+    draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, palette.text);
+
+    let (winner, color) = match summary.outcome {
+        Outcome::DefenderWin => ("Defenders win", palette.defender),
+        Outcome::AttackerWin => ("Attackers win", palette.attacker),
+        Outcome::Draw => ("Draw", palette.text),
+    };
+    let loser = match summary.outcome {
+        Outcome::DefenderWin => "attackers",
+        _ => "defenders",
+    };
+    let reason = match summary.reason {
+        EndReason::NoMoves => format!("the {loser} had no moves left"),
+        EndReason::FlagFell => format!("the {loser} ran out of time"),
+        reason => reason.to_string(),
+    };
+    let lines = [
+        reason,
+        format!("{} moves", summary.moves),
+        format!("{} attackers captured", summary.attackers_captured),
+        format!("{} defenders captured", summary.defenders_captured),
+    ];
+    let scale = layout.scale;
+    let x = panel.x + 24.0 * scale;
+    draw_text(winner, x, panel.y + 56.0 * scale, 48.0 * scale, color);
+    for (index, line) in lines.iter().enumerate() {
+        let y = panel.y + (100.0 + index as f32 * 28.0) * scale;
+        draw_text(line, x, y, 26.0 * scale, palette.text);
+    }
+    draw_buttons(&Command::GAME_OVER, layout, palette);
 }
 
-fn mouse_out_of_bounds() -> bool {
-    let (x, y) = mouse_position();
-    let window_range = SCREEN_EDGE..=BOARD_SIZE + SCREEN_EDGE;
-    !window_range.contains(&x) || !window_range.contains(&y)
+fn highlight_tile(tile: Tile, layout: &Layout, palette: &Palette) {
+    let rect = layout.tile_rect(tile);
+    let inset = 2.0 * layout.scale;
+    draw_rectangle_lines(
+        rect.x + inset,
+        rect.y + inset,
+        rect.w - 2.0 * inset,
+        rect.h - 2.0 * inset,
+        2.0,
+        palette.highlight,
+    )
 }
 
-// Option as mouse could be off of window
-fn mouse_tile_position() -> Option<Tile> {
-    if mouse_out_of_bounds() {
-        return None;
-    }
-    let (x, y) = mouse_position();
-    Some(Tile {
-        r: tile_index_from_mouse(x),
-        c: tile_index_from_mouse(y),
-    })
+// Option as mouse could be off of the board
+fn mouse_tile_position(layout: &Layout) -> Option<Tile> {
+    layout.tile_at(mouse_position().into())
 }
 
-fn draw_board(game: &Game) {
+fn draw_board(game: &Game, layout: &Layout, palette: &Palette) {
+    let board = layout.board();
+    draw_rectangle(board.x, board.y, board.w, board.h, palette.board);
+
     let tile_count = game.board_size();
+    let label_size = layout.tile_size() * 0.22;
     for r in 0..tile_count {
         for c in 0..tile_count {
-            let x = tile_position(r);
-            let y = tile_position(c);
-            draw_rectangle_lines(x, y, TILE_SIZE, TILE_SIZE, 2.0, BLUE)
+            let tile = Tile { r, c };
+            let rect = layout.tile_rect(tile);
+            // the throne gets a square inside and corners a cross, so they differ by more than colour
+            if tile == game.throne_tile() {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, palette.special);
+                let inset = rect.w * 0.15;
+                draw_rectangle_lines(
+                    rect.x + inset,
+                    rect.y + inset,
+                    rect.w - 2.0 * inset,
+                    rect.h - 2.0 * inset,
+                    2.0,
+                    palette.grid,
+                );
+            } else if game.is_corner(tile) {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, palette.special);
+                let inset = rect.w * 0.2;
+                let (left, right) = (rect.x + inset, rect.right() - inset);
+                let (top, bottom) = (rect.y + inset, rect.bottom() - inset);
+                draw_line(left, top, right, bottom, 2.0, palette.grid);
+                draw_line(left, bottom, right, top, 2.0, palette.grid);
+            }
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, palette.grid);
+
+            // ranks down the left and files along the bottom, as in the notation
+            let notation = tile.to_notation(tile_count);
+            let (file, rank) = notation.split_at(1);
+            let margin = rect.w * 0.06;
+            if r == 0 {
+                draw_text(
+                    rank,
+                    rect.x + margin,
+                    rect.y + margin + label_size * 0.7,
+                    label_size,
+                    palette.label,
+                );
+            }
+            if c == tile_count - 1 {
+                let width = measure_text(file, None, label_size as u16, 1.0).width;
+                draw_text(
+                    file,
+                    rect.right() - margin - width,
+                    rect.bottom() - margin,
+                    label_size,
+                    palette.label,
+                );
+            }
         }
     }
 }
 
-fn draw_pieces(game: &Game) {
+/// all but the `hidden` pieces, which are drawn moving or being dragged
+fn draw_pieces(game: &Game, layout: &Layout, palette: &Palette, hidden: &[Tile]) {
     for (r, row) in game.board.0.iter().enumerate() {
-        for (c, item) in row.iter().enumerate() {
-            use PieceType::*;
-            let color = match item {
-                King => WHITE,
-                Attacker => RED,
-                Defender => BLUE,
-                Blank => BLACK,
-            };
-            let x = tile_position(r) + TILE_SIZE / 2.0;
-            let y = tile_position(c) + TILE_SIZE / 2.0;
-            draw_circle(x, y, PIECE_SIZE, color);
+        for (c, &piece) in row.iter().enumerate() {
+            if piece != PieceType::Blank && !hidden.contains(&Tile { r, c }) {
+                let center = layout.tile_center(Tile { r, c });
+                let radius = layout.tile_size() * PIECE_SCALE;
+                draw_piece(piece, center, radius, palette, 1.0);
+            }
+        }
+    }
+}
+
+/// rings around the threatened pieces
+fn draw_threats(threatened: &[Tile], layout: &Layout, palette: &Palette) {
+    let radius = layout.tile_size() * PIECE_SCALE * 1.15;
+    for &tile in threatened {
+        let center = layout.tile_center(tile);
+        draw_circle_lines(
+            center.x,
+            center.y,
+            radius,
+            3.0 * layout.scale,
+            palette.threat,
+        );
+    }
+}
+
+fn draw_escape_routes(routes: &[Vec<Tile>], layout: &Layout, palette: &Palette) {
+    let width = layout.tile_size() * 0.08;
+    for route in routes {
+        for leg in route.windows(2) {
+            let (from, to) = (layout.tile_center(leg[0]), layout.tile_center(leg[1]));
+            draw_line(from.x, from.y, to.x, to.y, width, palette.escape);
         }
+        let corner = layout.tile_center(*route.last().unwrap());
+        draw_circle(corner.x, corner.y, width * 1.5, palette.escape);
+    }
+}
+
+/// A piece with a shadow and shine, attackers with an inner ring and the king crowned.
+/// `alpha` below 1 draws it see-through.
+fn draw_piece(piece: PieceType, center: Vec2, radius: f32, palette: &Palette, alpha: f32) {
+    let faded = |color: Color| Color {
+        a: color.a * alpha,
+        ..color
+    };
+    let color = match piece {
+        PieceType::King => palette.king,
+        PieceType::Attacker => palette.attacker,
+        PieceType::Defender => palette.defender,
+        PieceType::Blank => return,
+    };
+    let edge = (radius * 0.08).max(1.5);
+    let edge_color = faded(palette.piece_edge);
+    let shadow = center + vec2(0.08, 0.12) * radius;
+    draw_circle(
+        shadow.x,
+        shadow.y,
+        radius,
+        faded(Color::new(0.0, 0.0, 0.0, 0.35)),
+    );
+    draw_circle(center.x, center.y, radius, faded(color));
+    draw_circle_lines(center.x, center.y, radius, edge, edge_color);
+    let shine = center - vec2(0.35, 0.35) * radius;
+    draw_circle(
+        shine.x,
+        shine.y,
+        radius * 0.22,
+        faded(Color::new(1.0, 1.0, 1.0, 0.35)),
+    );
+
+    match piece {
+        PieceType::Attacker => {
+            draw_circle_lines(center.x, center.y, radius * 0.55, edge, edge_color)
+        }
+        PieceType::King => draw_crown(center, radius, palette),
+        _ => (),
+    }
+}
+
+fn draw_crown(center: Vec2, radius: f32, palette: &Palette) {
+    let point = |x: f32, y: f32| center + vec2(x, y) * radius;
+    let base = 0.15;
+    let (left, right) = (-0.55, 0.55);
+    draw_triangle(
+        point(left, base),
+        point(left, -0.35),
+        point(-0.1, base),
+        palette.crown,
+    );
+    draw_triangle(
+        point(-0.3, base),
+        point(0.0, -0.5),
+        point(0.3, base),
+        palette.crown,
+    );
+    draw_triangle(
+        point(0.1, base),
+        point(right, -0.35),
+        point(right, base),
+        palette.crown,
+    );
+    let band = point(left, base);
+    draw_rectangle(
+        band.x,
+        band.y,
+        (right - left) * radius,
+        0.25 * radius,
+        palette.crown,
+    );
+    for tip in [point(left, -0.35), point(0.0, -0.5), point(right, -0.35)] {
+        draw_circle(tip.x, tip.y, radius * 0.08, palette.piece_edge);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn setup_choices() {
+        let mut setup = Setup::default();
+        assert_eq!(setup.attacker.spec(), "human");
+        assert_eq!(setup.defender.spec(), "hard");
+        assert_eq!(setup.rows().len(), 6);
+
+        setup.change(SetupRow::AI(true), 1);
+        assert_eq!(setup.defender.spec(), "mcts:400");
+        setup.change(SetupRow::Strength(true), 2);
+        assert_eq!(setup.defender.spec(), "mcts:50");
+        setup.change(SetupRow::AI(true), -2);
+        assert_eq!(setup.defender.spec(), "greedy");
+        // no strength for the simple AIs
+        assert_eq!(setup.rows().len(), 5);
+
+        setup.change(SetupRow::Player(false), 1);
+        assert_eq!(setup.attacker.spec(), "hard");
+        assert_eq!(setup.rows().len(), 7);
+
+        setup.change(SetupRow::Theme, -1);
+        assert_eq!(setup.theme, Theme::HighContrast);
+    }
+
+    #[test]
+    fn overlay_toggles() {
+        let mut display = Display::new();
+        assert!(Overlay::ALL.iter().all(|&overlay| display.shows(overlay)));
+        display.toggle(Overlay::Threats);
+        assert!(!display.shows(Overlay::Threats));
+        assert!(display.shows(Overlay::KingPaths));
+        display.toggle(Overlay::Threats);
+        assert!(display.shows(Overlay::Threats));
+    }
+
+    #[test]
+    fn sliding_moves() {
+        let layout = Layout::new(SCREEN_WIDTH, SCREEN_HEIGHT, 7);
+        let sliding = Sliding {
+            mv: "d6-b6".parse().unwrap(),
+            piece: PieceType::Attacker,
+            start: 10.0,
+        };
+        let (src, dest) = (sliding.mv.src, sliding.mv.dest);
+        assert_eq!(sliding.center(&layout, 10.0), layout.tile_center(src));
+        assert_eq!(sliding.center(&layout, 20.0), layout.tile_center(dest));
+        // eased, so over half way at half time
+        let half = sliding.center(&layout, 10.0 + MOVE_TIME / 2.0);
+        let start = layout.tile_center(src);
+        assert!(half.distance(start) > layout.tile_center(dest).distance(start) / 2.0);
+        assert_eq!(progress(-1.0, MOVE_TIME), 0.0);
+    }
+
+    #[test]
+    fn move_list() {
+        let layout = Layout::new(SCREEN_WIDTH, SCREEN_HEIGHT, 7);
+        let mut panel = Panel {
+            moves: vec!["d6-b6".to_string(); 3],
+            ply: 3,
+            ..Panel::default()
+        };
+        let cells = panel.cells(&layout);
+        assert_eq!(
+            cells.iter().map(|(ply, _)| *ply).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        // pairs of moves share a row
+        assert_eq!(cells[1].1.y, cells[2].1.y);
+        assert!(cells[3].1.y > cells[2].1.y);
+
+        // the defenders' first move is on the right
+        panel.defenders_first = true;
+        let cells = panel.cells(&layout);
+        assert!(cells[1].1.x > cells[2].1.x);
+
+        // long games scroll to the current move
+        panel.moves = vec!["d6-b6".to_string(); 100];
+        panel.ply = 100;
+        let cells = panel.cells(&layout);
+        assert_eq!(cells.last().unwrap().0, 100);
+        assert!(cells.len() <= MOVE_ROWS * 2);
+        assert!(cells
+            .iter()
+            .all(|(_, cell)| cell.bottom() <= BOARD_TOP + BOARD_PIXELS));
+    }
+
+    #[test]
+    fn layout_scales() {
+        let layout = Layout::new(SCREEN_WIDTH, SCREEN_HEIGHT, 7);
+        assert_eq!(layout.scale, 1.0);
+        let tile = Tile { r: 2, c: 5 };
+        assert_eq!(layout.tile_at(layout.tile_center(tile)), Some(tile));
+        assert_eq!(layout.tile_at(vec2(5.0, 5.0)), None);
+
+        // a wide window is limited by its height, and is centered
+        let wide = Layout::new(SCREEN_WIDTH * 4.0, SCREEN_HEIGHT * 2.0, 11);
+        assert_eq!(wide.scale, 2.0);
+        assert_eq!(wide.origin.x, SCREEN_WIDTH);
+        let board = wide.board();
+        assert_eq!(board.x, SCREEN_WIDTH + SCREEN_EDGE * 2.0);
+        assert!(board.bottom() < SCREEN_HEIGHT * 2.0);
+        let corner = Tile { r: 10, c: 10 };
+        assert!((wide.tile_rect(corner).right() - board.right()).abs() < 1e-3);
+        assert_eq!(
+            wide.tile_at(board.point() + board.size() - 0.5),
+            Some(corner)
+        );
+        assert_eq!(wide.tile_at(board.point() + board.size() + 0.5), None);
     }
 }
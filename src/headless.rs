@@ -0,0 +1,201 @@
+//! Synchronous game loop without any rendering or input,
+//! for playing AI and scripted games to completion in tests and tools
+
+use crate::game::{Game, GameState, IllegalMove, Move, MoveFuture, Outcome, Player};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+/// Plays the given moves in order, then stops moving
+pub struct ScriptedPlayer {
+    moves: VecDeque<Move>,
+}
+
+impl ScriptedPlayer {
+    pub fn new(moves: impl IntoIterator<Item = Move>) -> Self {
+        ScriptedPlayer {
+            moves: moves.into_iter().collect(),
+        }
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn choose_move<'a>(&'a mut self, _game: &'a Game) -> MoveFuture<'a> {
+        Box::pin(std::future::ready(self.moves.pop_front()))
+    }
+}
+
+/// Runs a future on the current thread by polling until it is ready.
+/// Players that wait on something, like a human at the display, will spin.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        std::thread::yield_now();
+    }
+}
+
+/// Everything that happened in a headless game
+#[derive(Clone)]
+pub struct PlayedGame {
+    pub start: Game,
+    pub moves: Vec<Move>,
+    pub end: Game,
+    /// None if a player stopped moving before the game ended,
+    /// a draw if the ply limit was reached
+    pub outcome: Option<Outcome>,
+}
+
+/// Plays from `start` until the game ends, a player stops moving,
+/// or `max_plies` moves have been made, which counts as a draw.
+/// A side left without moves loses, as it can't play on.
+pub fn play_game(
+    start: Game,
+    defender: Box<dyn Player>,
+    attacker: Box<dyn Player>,
+    max_plies: usize,
+) -> Result<PlayedGame, IllegalMove> {
+    let mut state = GameState::from_game(start.clone(), defender, attacker);
+    let mut moves = Vec::new();
+    let mut stuck = false;
+    while !state.game.game_over && moves.len() < max_plies {
+        if state.game.get_all_valid_moves().next().is_none() {
+            stuck = true;
+            break;
+        }
+        match block_on(state.next_turn())? {
+            Some(report) => moves.push(report.mv),
+            None => {
+                return Ok(PlayedGame {
+                    start,
+                    moves,
+                    end: state.game,
+                    outcome: None,
+                })
+            }
+        }
+    }
+    let outcome = match state.game.outcome() {
+        Some(outcome) => outcome,
+        None if stuck && state.game.defenders_turn => Outcome::AttackerWin,
+        None if stuck => Outcome::DefenderWin,
+        None => Outcome::Draw,
+    };
+    if !state.game.game_over {
+        state.notify_game_ended();
+    }
+    Ok(PlayedGame {
+        start,
+        moves,
+        end: state.game,
+        outcome: Some(outcome),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ai::{AIKind, AIPlayer};
+    use crate::rng::Rng;
+
+    fn mv(notation: &str) -> Move {
+        notation.parse().unwrap()
+    }
+
+    #[test]
+    fn ai_games_finish() {
+        // seeded so the game is the same every run
+        let defender = Box::new(AIPlayer {
+            rng: Rng::new(1),
+            ..AIPlayer::new(AIKind::Minimax(1))
+        });
+        let attacker = Box::new(AIPlayer::new(AIKind::Random { seed: 5 }));
+        let played = play_game(Game::new(), defender, attacker, 300).unwrap();
+        assert_eq!(played.outcome, Some(Outcome::DefenderWin));
+        assert_eq!(played.end.outcome(), played.outcome);
+
+        // replaying the record reaches the same position
+        let mut game = played.start;
+        for mv in played.moves {
+            game = game.gen_next(mv.src, mv.dest);
+        }
+        assert_eq!(game.board, played.end.board);
+    }
+
+    #[test]
+    fn scripted_games() {
+        let defender = Box::new(ScriptedPlayer::new([mv("c4-c6")]));
+        let attacker = Box::new(ScriptedPlayer::new([mv("a4-a6"), mv("b4-b5")]));
+        let played = play_game(Game::new(), defender, attacker, 10).unwrap();
+        assert_eq!(played.moves.len(), 3);
+        assert_eq!(played.outcome, None);
+
+        let defender = Box::new(ScriptedPlayer::new([]));
+        let attacker = Box::new(ScriptedPlayer::new([mv("a4-d4")]));
+        let error = play_game(Game::new(), defender, attacker, 10).err();
+        assert_eq!(error.map(|error| error.mv), Some(mv("a4-d4")));
+
+        let defender = Box::new(AIPlayer::new(AIKind::Greedy));
+        let attacker = Box::new(AIPlayer::new(AIKind::Greedy));
+        let played = play_game(Game::new(), defender, attacker, 2).unwrap();
+        assert_eq!(played.moves.len(), 2);
+        assert_eq!(played.outcome, Some(Outcome::Draw));
+
+        // the last attacker is boxed in against a corner
+        let mut board = crate::game::Board::empty();
+        board.0[3][3] = crate::game::PieceType::King;
+        board.0[0][1] = crate::game::PieceType::Attacker;
+        board.0[0][2] = crate::game::PieceType::Defender;
+        board.0[1][1] = crate::game::PieceType::Defender;
+        let start = Game {
+            board,
+            defenders_turn: false,
+            game_over: false,
+            defender_won: false,
+        };
+        let defender = Box::new(ScriptedPlayer::new([]));
+        let attacker = Box::new(ScriptedPlayer::new([]));
+        let played = play_game(start, defender, attacker, 10).unwrap();
+        assert_eq!(played.outcome, Some(Outcome::DefenderWin));
+    }
+
+    #[test]
+    fn players_are_notified() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct Counting {
+            inner: AIPlayer,
+            opponent_moves: Rc<Cell<u32>>,
+            ended: Rc<Cell<bool>>,
+        }
+        impl Player for Counting {
+            fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a> {
+                self.inner.choose_move(game)
+            }
+            fn opponent_moved(&mut self, _game: &Game, _mv: Move) {
+                self.opponent_moves.set(self.opponent_moves.get() + 1);
+            }
+            fn game_ended(&mut self, _game: &Game) {
+                self.ended.set(true);
+            }
+        }
+
+        let opponent_moves = Rc::new(Cell::new(0));
+        let ended = Rc::new(Cell::new(false));
+        let defender = Box::new(Counting {
+            inner: AIPlayer::new(AIKind::Greedy),
+            opponent_moves: Rc::clone(&opponent_moves),
+            ended: Rc::clone(&ended),
+        });
+        let attacker = Box::new(AIPlayer::new(AIKind::Greedy));
+        play_game(Game::new(), defender, attacker, 5).unwrap();
+        // attackers moved on plies 1, 3 and 5
+        assert_eq!(opponent_moves.get(), 3);
+        assert!(ended.get());
+    }
+}
@@ -1,9 +1,63 @@
 use crate::{
-    game::{Game, Tile},
-    graphics::Display,
+    game::{Game, Move, MoveFuture, Player, Tile},
+    graphics::{BoardInput, Display},
 };
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::task::Poll;
+use std::time::Duration;
+
+/// Picks a move from presses and releases on the board, either clicking the piece
+/// and then where it goes or dragging it there
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveInput {
+    pub selected: Option<Tile>,
+    /// whether the selected piece is held down
+    pub dragging: bool,
+}
+
+impl MoveInput {
+    /// the move once one is made, which is always legal in `game`
+    pub fn handle(&mut self, game: &Game, input: BoardInput) -> Option<Move> {
+        match input {
+            BoardInput::Cancel => *self = MoveInput::default(),
+            // a press and release without moving between them
+            BoardInput::Choose(tile) => {
+                self.handle(game, BoardInput::Press(tile));
+                return self.handle(game, BoardInput::Release(Some(tile)));
+            }
+            BoardInput::Typed(mv) => {
+                if game.is_valid_move(mv) {
+                    *self = MoveInput::default();
+                    return Some(mv);
+                }
+            }
+            BoardInput::Press(tile) => {
+                // picking up a piece, or another one instead
+                if game.is_player_piece(tile) {
+                    self.selected = Some(tile);
+                    self.dragging = true;
+                }
+            }
+            BoardInput::Release(tile) => {
+                let dragged = std::mem::take(&mut self.dragging);
+                let src = self.selected?;
+                let dest = tile?;
+                let mv = Move { src, dest };
+                if game.is_valid_move(mv) {
+                    *self = MoveInput::default();
+                    return Some(mv);
+                }
+                // a drag that goes nowhere leaves the piece selected,
+                // clicking somewhere it can't go lets go of it
+                if !dragged && dest != src {
+                    self.selected = None;
+                }
+            }
+        }
+        None
+    }
+}
 
 pub struct HumanPlayer {
     display: Rc<RefCell<Display>>,
@@ -16,40 +70,202 @@ impl HumanPlayer {
         HumanPlayer { display }
     }
 
-    async fn next_click(&self, game: &Game) -> Tile {
-        self.display.borrow_mut().next_tile_click(game).await
+    async fn next_input(&self, game: &Game) -> Option<BoardInput> {
+        self.display.borrow_mut().next_board_input(game).await
     }
 
-    fn set_selected(&self, tile: Option<Tile>) {
-        self.display.borrow_mut().current_selection = tile;
+    fn show(&self, input: &MoveInput) {
+        let mut display = self.display.borrow_mut();
+        display.current_selection = input.selected;
+        display.dragging = input.selected.filter(|_| input.dragging);
     }
-    pub async fn player_turn(&self, game: &Game) -> (Tile, Tile) {
-        let mut last_src: Option<Tile> = None;
+
+    /// None if the display was given a command, like undo, instead of a move
+    pub async fn player_turn(&self, game: &Game) -> Option<(Tile, Tile)> {
+        let mut input = MoveInput::default();
         loop {
-            let src = match last_src {
-                Some(tile) => tile,
-                None => self.next_click(game).await,
+            self.show(&input);
+            let Some(board_input) = self.next_input(game).await else {
+                self.show(&MoveInput::default());
+                return None;
             };
-
-            if !game.is_player_piece(src) {
-                continue;
+            if let Some(mv) = input.handle(game, board_input) {
+                self.show(&input);
+                return Some((mv.src, mv.dest));
             }
-            self.set_selected(Some(src));
-
-            let dest = self.next_click(game).await;
-
-            // return valid move
-            let valid_moves: Vec<(Tile, Tile)> = game.get_valid_moves(src).collect();
-            if valid_moves.contains(&(src, dest)) {
-                self.set_selected(None);
-                return (src, dest);
-            } else {
-                // check if new player piece clicked on
-                if game.is_player_piece(dest) {
-                    last_src = Some(dest);
-                    self.set_selected(None);
-                }
+            if let BoardInput::Typed(mv) = board_input {
+                let notation = mv.to_notation(game.board_size());
+                self.display.borrow_mut().message = Some(format!("{notation} is not a legal move"));
             }
         }
     }
 }
+
+impl Player for HumanPlayer {
+    fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a> {
+        Box::pin(async move { self.player_turn(game).await.map(Move::from) })
+    }
+
+    fn is_human(&self) -> bool {
+        true
+    }
+
+    fn spec(&self) -> Option<String> {
+        Some("human".to_string())
+    }
+}
+
+/// Keeps the display drawing while another kind of player, like a remote
+/// one, is waiting for its move
+pub struct ShownPlayer {
+    inner: Box<dyn Player>,
+    display: Rc<RefCell<Display>>,
+}
+
+impl ShownPlayer {
+    pub fn new(inner: Box<dyn Player>, display: Rc<RefCell<Display>>) -> Self {
+        ShownPlayer { inner, display }
+    }
+}
+
+// the display stays borrowed while each frame is drawn, which is safe as the
+// game loop that also borrows it is waiting on this move until the frame ends
+#[allow(clippy::await_holding_refcell_ref)]
+impl Player for ShownPlayer {
+    fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a> {
+        Box::pin(async move {
+            let mut future = self.inner.choose_move(game);
+            loop {
+                let poll =
+                    std::future::poll_fn(|context| Poll::Ready(future.as_mut().poll(context)));
+                if let Poll::Ready(mv) = poll.await {
+                    drop(future);
+                    // say why it stopped, like a lost connection
+                    if let Some(error) = self.inner.error() {
+                        self.display.borrow_mut().message = Some(error);
+                    }
+                    return mv;
+                }
+                self.display.borrow_mut().draw_game(game).await;
+            }
+        })
+    }
+
+    fn opponent_moved(&mut self, game: &Game, mv: Move) {
+        self.inner.opponent_moved(game, mv);
+    }
+
+    fn game_ended(&mut self, game: &Game) {
+        self.inner.game_ended(game);
+    }
+
+    fn position_changed(&mut self, game: &Game) {
+        self.inner.position_changed(game);
+    }
+
+    fn time_budget(&mut self, budget: Duration) {
+        self.inner.time_budget(budget);
+    }
+
+    fn allows_undo(&self) -> bool {
+        self.inner.allows_undo()
+    }
+
+    fn error(&self) -> Option<String> {
+        self.inner.error()
+    }
+
+    fn evaluate(&self, game: &Game) -> Option<f32> {
+        self.inner.evaluate(game)
+    }
+
+    fn is_human(&self) -> bool {
+        self.inner.is_human()
+    }
+
+    fn spec(&self) -> Option<String> {
+        self.inner.spec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(notation: &str) -> Tile {
+        Tile::from_notation(notation, 7).unwrap()
+    }
+
+    #[test]
+    fn clicks_and_drags() {
+        let game = Game::new();
+        let mut input = MoveInput::default();
+        let mv = "d6-b6".parse::<Move>().unwrap();
+
+        // click the piece, then where it goes
+        assert_eq!(input.handle(&game, BoardInput::Press(tile("d6"))), None);
+        assert_eq!(
+            input.handle(&game, BoardInput::Release(Some(tile("d6")))),
+            None
+        );
+        assert_eq!(input.selected, Some(tile("d6")));
+        assert!(!input.dragging);
+        input.handle(&game, BoardInput::Press(tile("b6")));
+        assert_eq!(
+            input.handle(&game, BoardInput::Release(Some(tile("b6")))),
+            Some(mv)
+        );
+        assert_eq!(input, MoveInput::default());
+
+        // dragging it there
+        input.handle(&game, BoardInput::Press(tile("d6")));
+        assert!(input.dragging);
+        assert_eq!(
+            input.handle(&game, BoardInput::Release(Some(tile("b6")))),
+            Some(mv)
+        );
+
+        // dropped somewhere illegal it stays selected, cancelling lets go
+        input.handle(&game, BoardInput::Press(tile("d6")));
+        assert_eq!(
+            input.handle(&game, BoardInput::Release(Some(tile("d4")))),
+            None
+        );
+        assert_eq!(input.selected, Some(tile("d6")));
+        input.handle(&game, BoardInput::Cancel);
+        assert_eq!(input, MoveInput::default());
+
+        // the other side's pieces can't be picked up
+        input.handle(&game, BoardInput::Press(tile("d5")));
+        assert_eq!(input.selected, None);
+        // nor can a piece be dropped off the board
+        input.handle(&game, BoardInput::Press(tile("d6")));
+        assert_eq!(input.handle(&game, BoardInput::Release(None)), None);
+        // and clicking an illegal tile drops the selection
+        input.handle(&game, BoardInput::Press(tile("a1")));
+        assert_eq!(
+            input.handle(&game, BoardInput::Release(Some(tile("a1")))),
+            None
+        );
+        assert_eq!(input.selected, None);
+    }
+    #[test]
+    fn keyboard_moves() {
+        let game = Game::new();
+        let mut input = MoveInput::default();
+        let mv = "d6-b6".parse::<Move>().unwrap();
+
+        assert_eq!(input.handle(&game, BoardInput::Choose(tile("d6"))), None);
+        assert_eq!(input.selected, Some(tile("d6")));
+        assert!(!input.dragging);
+        assert_eq!(
+            input.handle(&game, BoardInput::Choose(tile("b6"))),
+            Some(mv)
+        );
+
+        // typed moves are checked the same way
+        let illegal = "d6-d5".parse().unwrap();
+        assert_eq!(input.handle(&game, BoardInput::Typed(illegal)), None);
+        assert_eq!(input.handle(&game, BoardInput::Typed(mv)), Some(mv));
+    }
+}
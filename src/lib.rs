@@ -1,9 +1,22 @@
 pub mod ai;
+pub mod clock;
+pub mod elo;
 pub mod game;
+#[cfg(feature = "gui")]
 pub mod graphics;
+pub mod headless;
+#[cfg(feature = "gui")]
 pub mod human;
+pub mod mcts;
+pub mod nn;
+pub mod notation;
+pub mod position;
+pub mod record;
+pub mod remote;
+pub mod rng;
 
 pub mod prelude {
-    pub use crate::game::{Game, PieceType, Tile};
+    pub use crate::game::{Game, Move, PieceType, Tile};
+    #[cfg(feature = "gui")]
     pub use crate::graphics::Display;
 }
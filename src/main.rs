@@ -3,43 +3,373 @@
 
 use std::cell::RefCell;
 use std::rc::Rc;
-use tafl::ai::{AIKind, AIPlayer};
-use tafl::game::{GameState, Player};
-use tafl::graphics::Display;
-use tafl::human::HumanPlayer;
+use std::time::Duration;
+use tafl::ai::AIPlayer;
+use tafl::clock::{self, Clock, TimeControl};
+use tafl::game::{Game, GameState, Player, Variant};
+use tafl::graphics::{Command, Display, Setup};
+use tafl::human::{HumanPlayer, ShownPlayer};
+use tafl::record::GameRecord;
+use tafl::remote::RemotePlayer;
+use tafl::rng::Rng;
 
-#[macroquad::main("Tafl")]
-async fn main() {
+/// where games are saved when no path is given on the command line
+const SAVE_PATH: &str = "tafl-save.pgn";
+
+struct Options {
+    /// player specs, from the saved game or the defaults when not given
+    attacker: Option<String>,
+    defender: Option<String>,
+    variant: Variant,
+    position: Option<String>,
+    /// thinking time for each AI move
+    move_time: Option<Duration>,
+    /// clocks for both sides, None for an untimed game
+    clock: Option<TimeControl>,
+    seed: Option<u64>,
+    saved_game: Option<String>,
+    /// describe each move in words, for screen readers
+    announce: bool,
+}
+
+const USAGE: &str = "usage: tafl [--attacker SIDE] [--defender SIDE] [--variant NAME] \
+[--position POSITION] [--move-time SECONDS] [--clock CONTROL] [--seed N] [--announce] [SAVED_GAME]
+  SIDE is human, remote:PORT to wait for a connection (remote:listen:HOST:PORT on
+  another interface), remote:HOST:PORT to connect,
+  or an AI such as minimax:4, mcts, random or a difficulty from beginner to expert
+  CONTROL is seconds each, like 300, with an increment like 300+5 or byoyomi like 300:30x3";
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        attacker: None,
+        defender: None,
+        variant: Variant::Brandubh,
+        position: None,
+        move_time: None,
+        clock: None,
+        seed: None,
+        saved_game: None,
+        announce: false,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.saved_game = Some(arg);
+            continue;
+        }
+        if arg == "--announce" {
+            options.announce = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {arg}"))?;
+        let bad_value = || format!("bad value for {arg}: {value}");
+        match arg.as_str() {
+            "--attacker" | "--defender" => {
+                // check AIs now rather than once the window is open
+                if value != "human" && !value.starts_with("remote:") {
+                    AIPlayer::from_spec(&value)?;
+                }
+                if arg == "--attacker" {
+                    options.attacker = Some(value);
+                } else {
+                    options.defender = Some(value);
+                }
+            }
+            "--variant" => options.variant = value.parse()?,
+            "--position" => options.position = Some(value),
+            "--move-time" => {
+                let seconds = value.parse::<f64>().map_err(|_| bad_value())?;
+                options.move_time =
+                    Some(Duration::try_from_secs_f64(seconds).map_err(|_| bad_value())?);
+            }
+            "--clock" => options.clock = Some(value.parse()?),
+            "--seed" => options.seed = Some(value.parse().map_err(|_| bad_value())?),
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    if options.position.is_some() && options.saved_game.is_some() {
+        return Err("a saved game already has a position".to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    // before opening the window so mistakes are reported straight away
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    macroquad::Window::from_config(tafl::graphics::window_conf(), run(options));
+}
+
+async fn run(options: Options) {
     let display = Rc::new(RefCell::new(Display::new()));
-    let depth = 4;
-    // players
-    // let attacker = Player::AI(AIPlayer {
-    //     kind: AIKind::Minimax(depth),
-    // });
-    let defender = Player::AI(AIPlayer {
-        kind: AIKind::Minimax(depth),
-    });
-    // let defender = Player::Human(HumanPlayer::new(Rc::clone(&display)));
-    let attacker = Player::Human(HumanPlayer::new(Rc::clone(&display)));
+    // a saved game is also where the game is saved to
+    let save_path = options.saved_game.clone().unwrap_or(SAVE_PATH.to_string());
 
-    let mut game_state = GameState::new(defender, attacker);
+    // the menu is skipped when the command line says who plays
+    let mut setup = Setup::default();
+    let game_state = match (&options.saved_game, &options.attacker, &options.defender) {
+        (Some(path), _, _) => load_game(path, &display, &options),
+        (None, None, None) => game_from_menu(&display, &options, &mut setup).await,
+        (None, attacker, defender) => new_game(
+            &display,
+            &options,
+            attacker.as_deref().unwrap_or("human"),
+            defender.as_deref().unwrap_or("minimax:4"),
+            options.variant,
+        ),
+    };
+    let mut game_state = game_state.unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
 
     // render game once, to show initial state
+    display.borrow_mut().show_state(&game_state);
     display.borrow_mut().draw_game(&game_state.game).await;
     display.borrow_mut().draw_game(&game_state.game).await;
 
     // Main graphics / input loop
     loop {
-        game_state.next_turn().await;
+        // let the last move finish before an AI holds up drawing with its search
+        let ai_to_move = !game_state.game.game_over && !game_state.current_player().is_human();
+        if ai_to_move && display.borrow().animating() {
+            display.borrow_mut().draw_game(&game_state.game).await;
+            continue;
+        }
+
+        let was_over = game_state.game.game_over;
+        // an AI moving in a position gone back to from the move list would throw
+        // away the moves after it, so it waits until the game is back to the latest
+        let reviewing = ai_to_move && game_state.can_redo();
+        if was_over || reviewing {
+            // nothing to play, wait for a command like the result panel, an undo or a redo
+            let command = display.borrow_mut().next_command(&game_state.game).await;
+            display.borrow_mut().command = Some(command);
+        } else {
+            let before = game_state.game.clone();
+            match game_state.next_turn().await {
+                Ok(Some(report)) => {
+                    println!("{report}");
+                    let mut display = display.borrow_mut();
+                    display.show_move(&before, &report);
+                    let description = report.describe(&before);
+                    // there are no options in the browser, so the page always gets it
+                    tafl::graphics::announce_on_page(&description);
+                    if options.announce {
+                        println!("{description}");
+                        display.message = Some(description);
+                    }
+                }
+                // the human gave a command instead of moving
+                Ok(None) => (),
+                Err(error) => {
+                    eprintln!("{error}");
+                    // keep showing the position the illegal move was made in
+                    loop {
+                        display.borrow_mut().draw_game(&game_state.game).await;
+                    }
+                }
+            }
+        }
 
-        if game_state.game.game_over {
-            break;
+        // whether the game was replaced rather than carried on
+        let mut replaced = false;
+        let command = display.borrow_mut().command.take();
+        match command {
+            Some(Command::Undo) => {
+                game_state.undo_turn();
+            }
+            Some(Command::Redo) => {
+                game_state.redo_turn();
+            }
+            Some(Command::Save) => {
+                let saved = GameRecord::from_state(&game_state)
+                    .map_err(|error| error.to_string())
+                    .and_then(|record| record.save(&save_path).map_err(|error| error.to_string()));
+                match saved {
+                    Ok(()) => println!("saved to {save_path}"),
+                    Err(error) => eprintln!("could not save {save_path}: {error}"),
+                }
+            }
+            Some(Command::Load) => match load_game(&save_path, &display, &options) {
+                Ok(loaded) => {
+                    game_state = loaded;
+                    replaced = true;
+                    println!("loaded {save_path}");
+                }
+                Err(error) => eprintln!("{error}"),
+            },
+            Some(Command::NewGame) => match game_from_menu(&display, &options, &mut setup).await {
+                Ok(new) => {
+                    game_state = new;
+                    replaced = true;
+                }
+                Err(error) => eprintln!("{error}"),
+            },
+            Some(command @ (Command::Rematch | Command::SwapSides)) => {
+                game_state = rematch(game_state, command == Command::SwapSides, &options);
+                replaced = true;
+            }
+            Some(Command::GoTo(ply)) => {
+                game_state.go_to(ply);
+            }
+            Some(Command::Review) => display.borrow_mut().result = None,
+            // the window closes once this function returns
+            Some(Command::Quit) => break,
+            None => (),
         }
+
+        if replaced
+            || matches!(
+                command,
+                Some(Command::Undo | Command::Redo | Command::GoTo(_))
+            )
+        {
+            display.borrow_mut().stop_animations();
+        }
+
+        // show the result when the game ends and hide it once play carries on
+        let is_over = game_state.game.game_over;
+        if is_over != was_over || replaced {
+            display.borrow_mut().result = game_state.summary();
+            if is_over && !replaced {
+                print_record(&game_state);
+            }
+        }
+        display.borrow_mut().show_state(&game_state);
         // render game
         display.borrow_mut().draw_game(&game_state.game).await;
     }
-    // display winner
-    loop {
-        display.borrow_mut().draw_game(&game_state.game).await;
+}
+
+fn print_record(game_state: &GameState) {
+    match GameRecord::from_state(game_state) {
+        Ok(record) => println!("\n{}", record.to_pgn()),
+        Err(error) => eprintln!("could not print the game: {error}"),
+    }
+}
+
+/// Builds a player from its spec, `seed` is used for AI players
+fn make_player(
+    spec: &str,
+    display: &Rc<RefCell<Display>>,
+    options: &Options,
+    seed: Option<u64>,
+) -> Result<Box<dyn Player>, String> {
+    if spec == "human" {
+        return Ok(Box::new(HumanPlayer::new(Rc::clone(display))));
+    }
+    if spec.starts_with("remote:") {
+        println!("connecting {spec}");
+        let remote = Box::new(RemotePlayer::from_spec(spec)?);
+        return Ok(Box::new(ShownPlayer::new(remote, Rc::clone(display))));
+    }
+    let mut player = AIPlayer::from_spec(spec)?;
+    if let Some(move_time) = options.move_time {
+        player.time_limit = Some(move_time);
+    }
+    if let Some(seed) = seed {
+        player.rng = Rng::new(seed);
+    }
+    Ok(Box::new(player))
+}
+
+/// defender and attacker
+type Players = (Box<dyn Player>, Box<dyn Player>);
+
+fn make_players(
+    display: &Rc<RefCell<Display>>,
+    options: &Options,
+    attacker: &str,
+    defender: &str,
+) -> Result<Players, String> {
+    // different seeds so two identical AIs don't mirror each other
+    let defender = make_player(defender, display, options, options.seed)?;
+    let attacker = make_player(
+        attacker,
+        display,
+        options,
+        options.seed.map(|seed| seed.wrapping_add(1)),
+    )?;
+    Ok((defender, attacker))
+}
+
+fn new_game(
+    display: &Rc<RefCell<Display>>,
+    options: &Options,
+    attacker: &str,
+    defender: &str,
+    variant: Variant,
+) -> Result<GameState, String> {
+    let game = match &options.position {
+        Some(position) => Game::from_position_str(position).map_err(|error| error.to_string())?,
+        None => Game::new(),
+    };
+    if game.variant() != variant {
+        return Err(format!("the position is {} not {variant}", game.variant()));
+    }
+    let (defender, attacker) = make_players(display, options, attacker, defender)?;
+    Ok(timed(
+        GameState::from_game(game, defender, attacker),
+        options,
+    ))
+}
+
+/// starts the clocks when the command line gave a time control and the game
+/// doesn't already have clocks saved with it
+fn timed(mut game_state: GameState, options: &Options) -> GameState {
+    if let (Some(control), None) = (options.clock, game_state.clock()) {
+        game_state.set_clock(Clock::new(control), clock::now);
+    }
+    game_state
+}
+
+/// The same players from the same start, or with the sides swapped. The
+/// players are kept rather than rebuilt, so a remote player keeps its connection.
+fn rematch(game_state: GameState, swap_sides: bool, options: &Options) -> GameState {
+    let start = game_state.start().clone();
+    let (mut defender, mut attacker) = game_state.into_players();
+    if swap_sides {
+        std::mem::swap(&mut attacker, &mut defender);
     }
+    timed(GameState::from_game(start, defender, attacker), options)
+}
+
+/// a new game with the sides picked on the setup menu
+async fn game_from_menu(
+    display: &Rc<RefCell<Display>>,
+    options: &Options,
+    setup: &mut Setup,
+) -> Result<GameState, String> {
+    display.borrow_mut().setup_menu(setup).await;
+    new_game(
+        display,
+        options,
+        &setup.attacker.spec(),
+        &setup.defender.spec(),
+        setup.variant,
+    )
+}
+
+fn load_game(
+    path: &str,
+    display: &Rc<RefCell<Display>>,
+    options: &Options,
+) -> Result<GameState, String> {
+    let record =
+        GameRecord::load(path).map_err(|error| format!("could not load {path}: {error}"))?;
+    // sides given on the command line replace the saved ones
+    let attacker = options.attacker.as_deref().unwrap_or(&record.attacker);
+    let defender = options.defender.as_deref().unwrap_or(&record.defender);
+    let (defender, attacker) = make_players(display, options, attacker, defender)?;
+    GameState::from_record(&record, defender, attacker)
+        .map(|game_state| timed(game_state, options))
+        .map_err(|error| format!("could not load {path}: {error}"))
 }
@@ -0,0 +1,289 @@
+//! PUCT tree search guided by a policy-value network, as in AlphaZero
+//!
+//! Without a network every move gets the same prior and positions are valued
+//! by how much `Game::score` changed since the root, giving a plain UCT style search.
+
+use crate::game::{Game, Tile};
+use crate::nn::{encode, tile_index, PolicySample, PolicyValueNet};
+use crate::rng::Rng;
+
+/// exploration constant in the PUCT formula
+const C_PUCT: f32 = 1.5;
+/// share of the root priors replaced by noise during self-play
+const ROOT_NOISE: f32 = 0.25;
+
+struct Node {
+    game: Game,
+    mv: Option<(Tile, Tile)>,
+    prior: f32,
+    visits: u32,
+    /// summed values from the view of the side that played `mv`
+    value_sum: f32,
+    children: Vec<usize>,
+}
+
+impl Node {
+    fn new(game: Game, mv: Option<(Tile, Tile)>, prior: f32) -> Self {
+        Node {
+            game,
+            mv,
+            prior,
+            visits: 0,
+            value_sum: 0.0,
+            children: Vec::new(),
+        }
+    }
+
+    fn mean_value(&self) -> f32 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.value_sum / self.visits as f32
+        }
+    }
+}
+
+pub struct Mcts<'a> {
+    network: Option<&'a PolicyValueNet>,
+    nodes: Vec<Node>,
+    root_score: i32,
+}
+
+impl<'a> Mcts<'a> {
+    pub fn new(game: &Game, network: Option<&'a PolicyValueNet>) -> Self {
+        Mcts {
+            network,
+            nodes: vec![Node::new(game.clone(), None, 1.0)],
+            root_score: game.score(),
+        }
+    }
+
+    /// Runs the simulations and returns every root move with its visit count.
+    /// `rng` mixes noise into the root priors, as done during self-play.
+    pub fn search(&mut self, simulations: u32, rng: Option<&Rng>) -> Vec<((Tile, Tile), u32)> {
+        self.search_while(simulations, rng, || true)
+    }
+
+    /// As `search`, stopping early once `keep_going` is false, such as when
+    /// the time for a move is up. At least one simulation is always run.
+    pub fn search_while(
+        &mut self,
+        simulations: u32,
+        rng: Option<&Rng>,
+        mut keep_going: impl FnMut() -> bool,
+    ) -> Vec<((Tile, Tile), u32)> {
+        for simulation in 0..simulations {
+            if simulation > 0 && !keep_going() {
+                break;
+            }
+            self.simulate();
+            if let (0, Some(rng)) = (simulation, rng) {
+                self.add_root_noise(rng);
+            }
+        }
+        self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| (self.nodes[child].mv.unwrap(), self.nodes[child].visits))
+            .collect()
+    }
+
+    fn simulate(&mut self) {
+        let mut path = vec![0];
+        let mut node = 0;
+        while !self.nodes[node].children.is_empty() {
+            node = self.select_child(node);
+            path.push(node);
+        }
+
+        let value = if self.nodes[node].game.game_over {
+            if self.nodes[node].game.defender_won {
+                1.0
+            } else {
+                -1.0
+            }
+        } else {
+            self.expand(node)
+        };
+
+        for node in path {
+            let node = &mut self.nodes[node];
+            node.visits += 1;
+            // the turn has already passed, so the mover is the other side
+            let mover_is_defender = !node.game.defenders_turn;
+            node.value_sum += if mover_is_defender { value } else { -value };
+        }
+    }
+
+    fn select_child(&self, node: usize) -> usize {
+        let parent = &self.nodes[node];
+        let exploration = C_PUCT * (parent.visits as f32).sqrt();
+        let puct = |child: &Node| {
+            child.mean_value() + exploration * child.prior / (1.0 + child.visits as f32)
+        };
+        *parent
+            .children
+            .iter()
+            .max_by(|&&a, &&b| puct(&self.nodes[a]).total_cmp(&puct(&self.nodes[b])))
+            .unwrap()
+    }
+
+    /// adds the children of a leaf, returning its value for the defenders
+    fn expand(&mut self, node: usize) -> f32 {
+        let game = self.nodes[node].game.clone();
+        let moves = game.get_all_valid_moves().collect::<Vec<(Tile, Tile)>>();
+        if moves.is_empty() {
+            // the side to move is stuck and loses
+            return if game.defenders_turn { -1.0 } else { 1.0 };
+        }
+        let (priors, value) = match self.network {
+            Some(network) => network.evaluate(&game, &moves),
+            None => {
+                let uniform = 1.0 / moves.len() as f32;
+                let change = (game.score() - self.root_score) as f32;
+                (vec![uniform; moves.len()], (change / 200.0).tanh())
+            }
+        };
+        for (&(src, dest), prior) in moves.iter().zip(priors) {
+            let child = Node::new(game.gen_next(src, dest), Some((src, dest)), prior);
+            self.nodes.push(child);
+            let child = self.nodes.len() - 1;
+            self.nodes[node].children.push(child);
+        }
+        value
+    }
+
+    /// Mean value of a root move after searching, from -1 for the attackers
+    /// winning to 1 for the defenders. None if it wasn't visited.
+    pub fn defender_value(&self, mv: (Tile, Tile)) -> Option<f32> {
+        let child = self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| &self.nodes[child])
+            .find(|child| child.mv == Some(mv) && child.visits > 0)?;
+        // values are from the view of the side that played the move
+        let mover_is_defender = self.nodes[0].game.defenders_turn;
+        let value = child.mean_value();
+        Some(if mover_is_defender { value } else { -value })
+    }
+
+    /// mixes exponential noise, a Dirichlet(1) sample once normalized, into the root priors
+    fn add_root_noise(&mut self, rng: &Rng) {
+        let children = self.nodes[0].children.clone();
+        let noise = children
+            .iter()
+            .map(|_| -(1.0 - rng.next_f32()).ln())
+            .collect::<Vec<f32>>();
+        let total = noise.iter().sum::<f32>();
+        for (child, noise) in children.into_iter().zip(noise) {
+            let prior = &mut self.nodes[child].prior;
+            *prior = (1.0 - ROOT_NOISE) * *prior + ROOT_NOISE * noise / total;
+        }
+    }
+}
+
+/// most visited move
+pub fn best_move(visits: &[((Tile, Tile), u32)]) -> (Tile, Tile) {
+    visits
+        .iter()
+        .max_by_key(|(_, visits)| *visits)
+        .expect("no valid moves")
+        .0
+}
+
+/// move picked in proportion to its visits
+pub fn sample_move(visits: &[((Tile, Tile), u32)], rng: &Rng) -> (Tile, Tile) {
+    let total = visits.iter().map(|(_, visits)| visits).sum::<u32>();
+    let mut pick = rng.below(total.max(1) as usize) as u32;
+    for &(mv, visits) in visits {
+        if pick < visits {
+            return mv;
+        }
+        pick -= visits;
+    }
+    best_move(visits)
+}
+
+/// One self-play game, a sample for every position.
+/// Moves are sampled by visit count for the first `exploration_plies` to vary the games.
+pub fn self_play_game(
+    network: Option<&PolicyValueNet>,
+    simulations: u32,
+    max_plies: usize,
+    rng: &Rng,
+) -> Vec<PolicySample> {
+    let exploration_plies = 8;
+    let mut game = Game::new();
+    let mut samples = Vec::new();
+    for ply in 0..max_plies {
+        if game.game_over {
+            break;
+        }
+        // a side left without moves can't play on, so it's scored as losing
+        if game.get_all_valid_moves().next().is_none() {
+            game.game_over = true;
+            game.defender_won = !game.defenders_turn;
+            break;
+        }
+        let visits = Mcts::new(&game, network).search(simulations, Some(rng));
+        let total = visits.iter().map(|(_, visits)| visits).sum::<u32>() as f32;
+        // too few simulations to visit any move leave nothing to learn from
+        if total > 0.0 {
+            samples.push(PolicySample {
+                input: encode(&game),
+                moves: visits
+                    .iter()
+                    .map(|&((src, dest), _)| (tile_index(&game, src), tile_index(&game, dest)))
+                    .collect(),
+                policy: visits
+                    .iter()
+                    .map(|&(_, visits)| visits as f32 / total)
+                    .collect(),
+                value: 0.0,
+            });
+        }
+        let (src, dest) = if ply < exploration_plies {
+            sample_move(&visits, rng)
+        } else {
+            best_move(&visits)
+        };
+        game = game.gen_next(src, dest);
+    }
+
+    let value = match (game.game_over, game.defender_won) {
+        (true, true) => 1.0,
+        (true, false) => -1.0,
+        (false, _) => 0.0,
+    };
+    for sample in samples.iter_mut() {
+        sample.value = value;
+    }
+    samples
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_king_escape() {
+        let game = Game::from_position_str("1a1k3/7/7/7/3a3/7/7 d brandubh").unwrap();
+
+        let visits = Mcts::new(&game, None).search(400, None);
+        let (src, dest) = best_move(&visits);
+        assert!(game.gen_next(src, dest).game_over);
+    }
+
+    #[test]
+    fn self_play_labels_positions() {
+        let network = PolicyValueNet::new(8, 0);
+        let samples = self_play_game(Some(&network), 8, 6, &Rng::new(1));
+        assert_eq!(samples.len(), 6);
+        for sample in samples {
+            assert_eq!(sample.moves.len(), sample.policy.len());
+            assert!((sample.policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        }
+        // a single simulation visits no moves, so there are no targets to learn
+        assert!(self_play_game(None, 1, 4, &Rng::new(1)).is_empty());
+    }
+}
@@ -0,0 +1,541 @@
+//! Small fully connected networks evaluated and trained on the cpu
+//!
+//! Weights files are plain text, a header line naming the network
+//! followed by each layer as `dense <inputs> <outputs>`,
+//! a line of weights (row per output) and a line of biases.
+
+use crate::ai::{AIKind, AIPlayer};
+use crate::game::{Game, PieceType, Tile};
+use crate::rng::Rng;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+const PLANES: usize = 3;
+const CELLS: usize = 7 * 7;
+/// one plane per piece type plus the side to move
+pub const INPUTS: usize = PLANES * CELLS + 1;
+
+const VALUE_HEADER: &str = "tafl-nn value v1";
+const POLICY_VALUE_HEADER: &str = "tafl-nn policy-value v1";
+
+/// Board planes for attackers, defenders and the king, then 1.0 if defenders move
+pub fn encode(game: &Game) -> Vec<f32> {
+    let mut input = vec![0.0; INPUTS];
+    for (r, row) in game.board.0.iter().enumerate() {
+        for (c, piece) in row.iter().enumerate() {
+            let plane = match piece {
+                PieceType::Attacker => 0,
+                PieceType::Defender => 1,
+                PieceType::King => 2,
+                PieceType::Blank => continue,
+            };
+            input[plane * CELLS + tile_index(game, (r, c).into())] = 1.0;
+        }
+    }
+    if game.defenders_turn {
+        input[PLANES * CELLS] = 1.0;
+    }
+    input
+}
+
+/// Fully connected layer, weights stored a row per output
+#[derive(Clone, Debug)]
+pub struct Dense {
+    inputs: usize,
+    outputs: usize,
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+impl Dense {
+    /// uniform initialization scaled by fan in
+    pub fn new(inputs: usize, outputs: usize, rng: &Rng) -> Self {
+        let limit = (6.0 / inputs as f32).sqrt();
+        Dense {
+            inputs,
+            outputs,
+            weights: (0..inputs * outputs)
+                .map(|_| (rng.next_f32() * 2.0 - 1.0) * limit)
+                .collect(),
+            bias: vec![0.0; outputs],
+        }
+    }
+
+    /// same shape as `self` with every value zero, used to accumulate gradients
+    pub fn zeros_like(&self) -> Self {
+        Dense {
+            inputs: self.inputs,
+            outputs: self.outputs,
+            weights: vec![0.0; self.weights.len()],
+            bias: vec![0.0; self.outputs],
+        }
+    }
+
+    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
+        self.weights
+            .chunks(self.inputs)
+            .zip(&self.bias)
+            .map(|(row, bias)| row.iter().zip(input).map(|(w, x)| w * x).sum::<f32>() + bias)
+            .collect()
+    }
+
+    /// Adds the gradients for one example to `grads`, returns the gradient of the input
+    pub fn backward(&self, input: &[f32], grad_out: &[f32], grads: &mut Dense) -> Vec<f32> {
+        let mut grad_in = vec![0.0; self.inputs];
+        for (o, &g) in grad_out.iter().enumerate() {
+            if g == 0.0 {
+                continue;
+            }
+            let row = o * self.inputs;
+            for (i, &x) in input.iter().enumerate() {
+                grads.weights[row + i] += g * x;
+                grad_in[i] += g * self.weights[row + i];
+            }
+            grads.bias[o] += g;
+        }
+        grad_in
+    }
+
+    /// gradient descent step, grads summed over a batch are divided by batch size
+    pub fn step(&mut self, grads: &Dense, learning_rate: f32, batch_size: usize) {
+        let rate = learning_rate / batch_size as f32;
+        for (w, g) in self.weights.iter_mut().zip(&grads.weights) {
+            *w -= rate * g;
+        }
+        for (b, g) in self.bias.iter_mut().zip(&grads.bias) {
+            *b -= rate * g;
+        }
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "dense {} {}", self.inputs, self.outputs)?;
+        writeln!(out, "{}", join(&self.weights))?;
+        writeln!(out, "{}", join(&self.bias))
+    }
+
+    pub fn read(lines: &mut impl Iterator<Item = io::Result<String>>) -> io::Result<Self> {
+        let header = next_line(lines)?;
+        let sizes = header
+            .strip_prefix("dense ")
+            .ok_or_else(|| invalid("expected dense layer"))?
+            .split_whitespace()
+            .map(|size| size.parse::<usize>().map_err(|_| invalid("bad layer size")))
+            .collect::<io::Result<Vec<usize>>>()?;
+        let [inputs, outputs] = sizes[..] else {
+            return Err(invalid("dense layer needs inputs and outputs"));
+        };
+        let weights = parse_floats(&next_line(lines)?, inputs * outputs)?;
+        let bias = parse_floats(&next_line(lines)?, outputs)?;
+        Ok(Dense {
+            inputs,
+            outputs,
+            weights,
+            bias,
+        })
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+}
+
+pub fn relu(values: &mut [f32]) {
+    for value in values {
+        *value = value.max(0.0);
+    }
+}
+
+/// zeroes gradients where the relu was inactive
+pub fn relu_backward(activations: &[f32], grads: &mut [f32]) {
+    for (grad, activation) in grads.iter_mut().zip(activations) {
+        if *activation <= 0.0 {
+            *grad = 0.0;
+        }
+    }
+}
+
+fn join(values: &[f32]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_floats(line: &str, expected: usize) -> io::Result<Vec<f32>> {
+    let values = line
+        .split_whitespace()
+        .map(|value| value.parse::<f32>().map_err(|_| invalid("bad weight")))
+        .collect::<io::Result<Vec<f32>>>()?;
+    if values.len() != expected {
+        return Err(invalid("wrong number of weights"));
+    }
+    Ok(values)
+}
+
+pub fn next_line(lines: &mut impl Iterator<Item = io::Result<String>>) -> io::Result<String> {
+    lines
+        .next()
+        .unwrap_or_else(|| Err(invalid("unexpected end of weights file")))
+}
+
+pub fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A position labelled with how the game it came from ended
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub input: Vec<f32>,
+    /// 1.0 defender win, -1.0 attacker win, 0.0 unfinished
+    pub value: f32,
+}
+
+/// Value network, board planes in and the expected result for the defenders out
+#[derive(Clone, Debug)]
+pub struct Network {
+    hidden: Dense,
+    output: Dense,
+}
+
+impl Network {
+    pub fn new(hidden: usize, seed: u64) -> Self {
+        let rng = Rng::new(seed);
+        Network {
+            hidden: Dense::new(INPUTS, hidden, &rng),
+            output: Dense::new(hidden, 1, &rng),
+        }
+    }
+
+    /// expected result in -1.0..=1.0, positive favours the defenders
+    pub fn evaluate(&self, game: &Game) -> f32 {
+        self.forward(&encode(game)).1
+    }
+
+    fn forward(&self, input: &[f32]) -> (Vec<f32>, f32) {
+        let mut hidden = self.hidden.forward(input);
+        relu(&mut hidden);
+        let value = self.output.forward(&hidden)[0].tanh();
+        (hidden, value)
+    }
+
+    /// One gradient descent step on squared error, returns the mean loss
+    pub fn train_batch(&mut self, batch: &[Sample], learning_rate: f32) -> f32 {
+        let mut hidden_grads = self.hidden.zeros_like();
+        let mut output_grads = self.output.zeros_like();
+        let mut loss = 0.0;
+        for sample in batch {
+            let (hidden, value) = self.forward(&sample.input);
+            let error = value - sample.value;
+            loss += error * error;
+            // through the tanh
+            let grad = 2.0 * error * (1.0 - value * value);
+            let mut grad_hidden = self.output.backward(&hidden, &[grad], &mut output_grads);
+            relu_backward(&hidden, &mut grad_hidden);
+            self.hidden
+                .backward(&sample.input, &grad_hidden, &mut hidden_grads);
+        }
+        self.hidden.step(&hidden_grads, learning_rate, batch.len());
+        self.output.step(&output_grads, learning_rate, batch.len());
+        loss / batch.len() as f32
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", VALUE_HEADER)?;
+        self.hidden.write(&mut out)?;
+        self.output.write(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        if next_line(&mut lines)? != VALUE_HEADER {
+            return Err(invalid("not a tafl value network"));
+        }
+        let hidden = Dense::read(&mut lines)?;
+        let output = Dense::read(&mut lines)?;
+        if hidden.inputs() != INPUTS || output.inputs() != hidden.outputs() || output.outputs() != 1
+        {
+            return Err(invalid("layer sizes do not fit together"));
+        }
+        Ok(Network { hidden, output })
+    }
+}
+
+/// Index of a tile in the board planes and policy heads
+pub fn tile_index(game: &Game, tile: Tile) -> usize {
+    tile.r * game.board_size() + tile.c
+}
+
+/// A position with the search's visit distribution and the game result
+#[derive(Clone, Debug)]
+pub struct PolicySample {
+    pub input: Vec<f32>,
+    /// legal moves as (src, dest) tile indices
+    pub moves: Vec<(usize, usize)>,
+    /// target probability of each legal move
+    pub policy: Vec<f32>,
+    /// 1.0 defender win, -1.0 attacker win, 0.0 unfinished
+    pub value: f32,
+}
+
+/// Network with a shared hidden layer, a value head and a policy head.
+/// The policy is factored as a source logit plus a destination logit,
+/// so only 2 * 49 outputs are needed for the (src, dest) move space.
+#[derive(Clone, Debug)]
+pub struct PolicyValueNet {
+    hidden: Dense,
+    src: Dense,
+    dest: Dense,
+    value: Dense,
+}
+
+impl PolicyValueNet {
+    pub fn new(hidden: usize, seed: u64) -> Self {
+        let rng = Rng::new(seed);
+        PolicyValueNet {
+            hidden: Dense::new(INPUTS, hidden, &rng),
+            src: Dense::new(hidden, CELLS, &rng),
+            dest: Dense::new(hidden, CELLS, &rng),
+            value: Dense::new(hidden, 1, &rng),
+        }
+    }
+
+    /// Probability of each of `moves` and the expected result for the defenders
+    pub fn evaluate(&self, game: &Game, moves: &[(Tile, Tile)]) -> (Vec<f32>, f32) {
+        let indices = moves
+            .iter()
+            .map(|&(src, dest)| (tile_index(game, src), tile_index(game, dest)))
+            .collect::<Vec<_>>();
+        let forward = self.forward(&encode(game), &indices);
+        (forward.policy, forward.value)
+    }
+
+    fn forward(&self, input: &[f32], moves: &[(usize, usize)]) -> Forward {
+        let mut hidden = self.hidden.forward(input);
+        relu(&mut hidden);
+        let src = self.src.forward(&hidden);
+        let dest = self.dest.forward(&hidden);
+        let logits = moves
+            .iter()
+            .map(|&(s, d)| src[s] + dest[d])
+            .collect::<Vec<f32>>();
+        let value = self.value.forward(&hidden)[0].tanh();
+        Forward {
+            policy: softmax(&logits),
+            value,
+            hidden,
+        }
+    }
+
+    /// One gradient descent step on squared value error plus policy cross entropy,
+    /// returns the mean loss
+    pub fn train_batch(&mut self, batch: &[PolicySample], learning_rate: f32) -> f32 {
+        let mut hidden_grads = self.hidden.zeros_like();
+        let mut src_grads = self.src.zeros_like();
+        let mut dest_grads = self.dest.zeros_like();
+        let mut value_grads = self.value.zeros_like();
+        let mut loss = 0.0;
+        for sample in batch {
+            let forward = self.forward(&sample.input, &sample.moves);
+
+            let error = forward.value - sample.value;
+            loss += error * error;
+            let value_grad = 2.0 * error * (1.0 - forward.value * forward.value);
+
+            // softmax cross entropy gradient is predicted minus target
+            let mut src_grad = vec![0.0; CELLS];
+            let mut dest_grad = vec![0.0; CELLS];
+            for ((&(s, d), &p), &target) in
+                sample.moves.iter().zip(&forward.policy).zip(&sample.policy)
+            {
+                loss -= target * p.max(1e-7).ln();
+                src_grad[s] += p - target;
+                dest_grad[d] += p - target;
+            }
+
+            let hidden = &forward.hidden;
+            let mut grad_hidden = self.value.backward(hidden, &[value_grad], &mut value_grads);
+            let from_src = self.src.backward(hidden, &src_grad, &mut src_grads);
+            let from_dest = self.dest.backward(hidden, &dest_grad, &mut dest_grads);
+            for ((grad, a), b) in grad_hidden.iter_mut().zip(from_src).zip(from_dest) {
+                *grad += a + b;
+            }
+            relu_backward(hidden, &mut grad_hidden);
+            self.hidden
+                .backward(&sample.input, &grad_hidden, &mut hidden_grads);
+        }
+        self.hidden.step(&hidden_grads, learning_rate, batch.len());
+        self.src.step(&src_grads, learning_rate, batch.len());
+        self.dest.step(&dest_grads, learning_rate, batch.len());
+        self.value.step(&value_grads, learning_rate, batch.len());
+        loss / batch.len() as f32
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", POLICY_VALUE_HEADER)?;
+        for layer in [&self.hidden, &self.src, &self.dest, &self.value] {
+            layer.write(&mut out)?;
+        }
+        out.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        if next_line(&mut lines)? != POLICY_VALUE_HEADER {
+            return Err(invalid("not a tafl policy-value network"));
+        }
+        let hidden = Dense::read(&mut lines)?;
+        let src = Dense::read(&mut lines)?;
+        let dest = Dense::read(&mut lines)?;
+        let value = Dense::read(&mut lines)?;
+        let heads_fit = [&src, &dest, &value]
+            .iter()
+            .all(|head| head.inputs() == hidden.outputs());
+        if hidden.inputs() != INPUTS
+            || !heads_fit
+            || src.outputs() != CELLS
+            || dest.outputs() != CELLS
+            || value.outputs() != 1
+        {
+            return Err(invalid("layer sizes do not fit together"));
+        }
+        Ok(PolicyValueNet {
+            hidden,
+            src,
+            dest,
+            value,
+        })
+    }
+}
+
+struct Forward {
+    policy: Vec<f32>,
+    value: f32,
+    hidden: Vec<f32>,
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps = logits
+        .iter()
+        .map(|logit| (logit - max).exp())
+        .collect::<Vec<f32>>();
+    let total = exps.iter().sum::<f32>();
+    exps.into_iter().map(|e| e / total).collect()
+}
+
+/// Plays minimax against itself, labelling every position with the result.
+/// The first few moves are random so the games differ.
+pub fn self_play(games: usize, depth: u32, max_plies: usize, seed: u64) -> Vec<Sample> {
+    let rng = Rng::new(seed);
+    let player = AIPlayer {
+        proof_search: None,
+        selection_margin: 5,
+        rng: Rng::new(seed.wrapping_add(1)),
+        ..AIPlayer::new(AIKind::Minimax(depth))
+    };
+    let opening_plies = 4;
+
+    let mut samples = Vec::new();
+    for _ in 0..games {
+        let mut game = Game::new();
+        let mut inputs = Vec::new();
+        for ply in 0..max_plies {
+            if game.game_over {
+                break;
+            }
+            // a side left without moves can't play on, so it's scored as losing
+            if game.get_all_valid_moves().next().is_none() {
+                game.game_over = true;
+                game.defender_won = !game.defenders_turn;
+                break;
+            }
+            inputs.push(encode(&game));
+            let (src, dest) = if ply < opening_plies {
+                let moves = game.get_all_valid_moves().collect::<Vec<_>>();
+                moves[rng.below(moves.len())]
+            } else {
+                player.take_turn(&game)
+            };
+            game = game.gen_next(src, dest);
+        }
+        let value = match (game.game_over, game.defender_won) {
+            (true, true) => 1.0,
+            (true, false) => -1.0,
+            (false, _) => 0.0,
+        };
+        samples.extend(inputs.into_iter().map(|input| Sample { input, value }));
+    }
+    samples
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn learns_simple_targets() {
+        let start = Game::new();
+        let moved = start.gen_next((3, 1).into(), (2, 1).into());
+        let batch = vec![
+            Sample {
+                input: encode(&start),
+                value: 0.5,
+            },
+            Sample {
+                input: encode(&moved),
+                value: -0.5,
+            },
+        ];
+        let mut network = Network::new(16, 1);
+        for _ in 0..500 {
+            network.train_batch(&batch, 0.05);
+        }
+        assert!((network.evaluate(&start) - 0.5).abs() < 0.1);
+        assert!((network.evaluate(&moved) + 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        let network = Network::new(8, 2);
+        let path = std::env::temp_dir().join("tafl_weights_round_trip.nn");
+        network.save(&path).unwrap();
+        let loaded = Network::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let game = Game::new();
+        assert_eq!(network.evaluate(&game), loaded.evaluate(&game));
+    }
+
+    #[test]
+    fn policy_learns_target_move() {
+        let game = Game::new();
+        let moves = game.get_all_valid_moves().collect::<Vec<_>>();
+        let mut policy = vec![0.0; moves.len()];
+        policy[3] = 1.0;
+        let sample = PolicySample {
+            input: encode(&game),
+            moves: moves
+                .iter()
+                .map(|&(src, dest)| (tile_index(&game, src), tile_index(&game, dest)))
+                .collect(),
+            policy,
+            value: 1.0,
+        };
+        let mut network = PolicyValueNet::new(16, 3);
+        for _ in 0..300 {
+            network.train_batch(std::slice::from_ref(&sample), 0.05);
+        }
+        let (probabilities, value) = network.evaluate(&game, &moves);
+        assert!(probabilities[3] > 0.5);
+        assert!(value > 0.8);
+    }
+}
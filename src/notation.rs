@@ -0,0 +1,219 @@
+//! Algebraic notation for tiles and moves
+//!
+//! Files are letters from `a` on the left of the display and ranks are numbers
+//! from 1 at the bottom, so on brandubh the top left corner is `a7`.
+//! A tile's `r` is its file and its `c` counts ranks down from the top.
+//! Moves are written `d1-d3`, followed by each capture such as `xe3`.
+
+use crate::game::{Game, Move, MoveReport, PieceType, Tile, BOARD_SIZE};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NotationError {
+    /// not a file letter followed by a rank number on the board
+    Tile(String),
+    /// not two tiles joined by `-`
+    Move(String),
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::Tile(text) => write!(f, "not a tile: {text}"),
+            NotationError::Move(text) => write!(f, "not a move: {text}"),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl Tile {
+    /// notation on a board with `size` files and ranks
+    pub fn to_notation(&self, size: usize) -> String {
+        let file = (b'a' + self.r as u8) as char;
+        format!("{}{}", file, size - self.c)
+    }
+
+    /// parses notation on a board with `size` files and ranks
+    pub fn from_notation(text: &str, size: usize) -> Result<Tile, NotationError> {
+        let error = || NotationError::Tile(text.to_string());
+        let mut chars = text.chars();
+        let file = chars.next().ok_or_else(error)?;
+        let rank = chars.as_str().parse::<usize>().map_err(|_| error())?;
+        if !file.is_ascii_lowercase() || chars.as_str().starts_with('+') {
+            return Err(error());
+        }
+        let r = (file as u8 - b'a') as usize;
+        if r >= size || rank == 0 || rank > size {
+            return Err(error());
+        }
+        Ok(Tile { r, c: size - rank })
+    }
+}
+
+impl Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_notation(BOARD_SIZE))
+    }
+}
+
+impl FromStr for Tile {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Tile::from_notation(s, BOARD_SIZE)
+    }
+}
+
+impl Move {
+    pub fn to_notation(&self, size: usize) -> String {
+        format!(
+            "{}-{}",
+            self.src.to_notation(size),
+            self.dest.to_notation(size)
+        )
+    }
+
+    /// Parses `src-dest`, any captures written after it are checked but ignored
+    /// as the rules decide what gets captured
+    pub fn from_notation(text: &str, size: usize) -> Result<Move, NotationError> {
+        let error = || NotationError::Move(text.to_string());
+        let mut parts = text.trim().split('x');
+        let (src, dest) = parts
+            .next()
+            .and_then(|mv| mv.split_once('-'))
+            .ok_or_else(error)?;
+        for capture in parts {
+            Tile::from_notation(capture, size)?;
+        }
+        Ok(Move {
+            src: Tile::from_notation(src, size)?,
+            dest: Tile::from_notation(dest, size)?,
+        })
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_notation(BOARD_SIZE))
+    }
+}
+
+impl FromStr for Move {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Move::from_notation(s, BOARD_SIZE)
+    }
+}
+
+impl MoveReport {
+    /// the move followed by its captures, like `d1-d3xe3`
+    pub fn to_notation(&self, size: usize) -> String {
+        let mut text = self.mv.to_notation(size);
+        for capture in &self.captures {
+            text.push('x');
+            text.push_str(&capture.to_notation(size));
+        }
+        text
+    }
+
+    /// The move in words for reading out, like `attacker c2 to c3, taking the defender on c4`.
+    /// `before` is the game the move was played in.
+    pub fn describe(&self, before: &Game) -> String {
+        let size = before.board_size();
+        let name = |tile: Tile| match before.board.0[tile.r][tile.c] {
+            PieceType::Attacker => "attacker",
+            PieceType::Defender => "defender",
+            PieceType::King => "king",
+            PieceType::Blank => "empty tile",
+        };
+        let Move { src, dest } = self.mv;
+        let mut text = format!(
+            "{} {} to {}",
+            name(src),
+            src.to_notation(size),
+            dest.to_notation(size)
+        );
+        for &capture in &self.captures {
+            text.push_str(&format!(
+                ", taking the {} on {}",
+                name(capture),
+                capture.to_notation(size)
+            ));
+        }
+        if before.board.0[src.r][src.c] == PieceType::King && before.is_corner(dest) {
+            text.push_str(", escaping");
+        }
+        text
+    }
+}
+
+impl Display for MoveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_notation(BOARD_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn tiles() {
+        let corner: Tile = "a7".parse().unwrap();
+        assert_eq!(corner, Tile { r: 0, c: 0 });
+        assert_eq!(Tile { r: 6, c: 6 }.to_string(), "g1");
+        assert_eq!(Tile { r: 3, c: 3 }.to_string(), "d4");
+        // larger boards use more files and ranks
+        assert_eq!(Tile::from_notation("k11", 11), Ok(Tile { r: 10, c: 0 }));
+        assert_eq!(Tile { r: 10, c: 10 }.to_notation(11), "k1");
+
+        for bad in ["", "a", "h1", "a0", "a8", "A1", "1a", "a+1", "a1b"] {
+            assert!(bad.parse::<Tile>().is_err(), "{bad} parsed");
+        }
+    }
+
+    #[test]
+    fn moves() {
+        let mv: Move = "d6-b6".parse().unwrap();
+        assert_eq!(mv.src, Tile { r: 3, c: 1 });
+        assert_eq!(mv.dest, Tile { r: 1, c: 1 });
+        assert_eq!(mv.to_string(), "d6-b6");
+        assert_eq!("d6-b6xb5".parse::<Move>(), Ok(mv));
+
+        for bad in ["d6", "d6b6", "d6-", "d6-b6x", "d6-b6xz9"] {
+            assert!(bad.parse::<Move>().is_err(), "{bad} parsed");
+        }
+    }
+
+    #[test]
+    fn reports_captures() {
+        let mut game = Game::new();
+        for mv in ["d6-c6", "e4-e6", "c6-c5", "e6-e7", "d2-c2", "e7-f7"] {
+            let (next, report) = game.apply_move(mv.parse().unwrap());
+            assert!(report.captures.is_empty());
+            assert_eq!(report.to_string(), mv);
+            game = next;
+        }
+        // c4 ends up between attackers on c5 and c3
+        let (game, report) = game.apply_move("c2-c3".parse().unwrap());
+        assert_eq!(report.captures, vec!["c4".parse().unwrap()]);
+        assert_eq!(report.to_string(), "c2-c3xc4");
+        assert_eq!(game.board.0[2][3], crate::game::PieceType::Blank);
+    }
+
+    #[test]
+    fn describes_moves() {
+        let game = Game::from_position_str("3k3/7/1ad4/7/d6/7/3a3 d brandubh").unwrap();
+        let (_, report) = game.apply_move("a3-a5".parse().unwrap());
+        assert_eq!(
+            report.describe(&game),
+            "defender a3 to a5, taking the attacker on b5"
+        );
+        let (_, report) = game.apply_move("d7-g7".parse().unwrap());
+        assert_eq!(report.describe(&game), "king d7 to g7, escaping");
+    }
+}
@@ -0,0 +1,280 @@
+//! Text format for positions, similar to FEN in chess
+//!
+//! `3a3/3a3/3d3/aadkdaa/3d3/3a3/3a3 a brandubh` is the starting position.
+//! Ranks are listed from the top of the board down, each from the `a` file
+//! across, with `a` for attackers, `d` for defenders, `k` for the king and
+//! numbers for runs of empty tiles. Then comes the side to move, `a` or `d`,
+//! and the variant.
+
+use crate::game::{Board, Game, PieceType, Tile, Variant};
+use std::fmt::{self, Display};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PositionError {
+    /// board, side to move and variant are all needed
+    MissingField,
+    UnknownVariant(String),
+    BadSide(String),
+    WrongRankCount {
+        expected: usize,
+        found: usize,
+    },
+    /// a rank with the wrong number of files or an unknown piece letter
+    BadRank(String),
+    KingCount(usize),
+    /// a piece other than the king on the throne or a corner
+    RestrictedTile(Tile),
+    /// more pieces of a kind than the variant starts with
+    TooMany(PieceType),
+}
+
+impl Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::MissingField => {
+                write!(f, "position needs a board, side to move and variant")
+            }
+            PositionError::UnknownVariant(variant) => write!(f, "unknown variant {variant}"),
+            PositionError::BadSide(side) => write!(f, "side to move must be a or d, not {side}"),
+            PositionError::WrongRankCount { expected, found } => {
+                write!(f, "expected {expected} ranks but found {found}")
+            }
+            PositionError::BadRank(rank) => write!(f, "bad rank {rank}"),
+            PositionError::KingCount(count) => write!(f, "expected one king but found {count}"),
+            PositionError::RestrictedTile(tile) => {
+                write!(f, "only the king may stand on {tile}")
+            }
+            PositionError::TooMany(piece) => write!(f, "too many pieces of type {piece}"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+fn piece_letter(piece: PieceType) -> char {
+    match piece {
+        PieceType::Attacker => 'a',
+        PieceType::Defender => 'd',
+        PieceType::King => 'k',
+        PieceType::Blank => ' ',
+    }
+}
+
+impl Game {
+    pub fn to_position_str(&self) -> String {
+        let size = self.board_size();
+        let ranks = (0..size)
+            .map(|c| {
+                let mut rank = String::new();
+                let mut empty = 0;
+                for r in 0..size {
+                    let piece = self.board.0[r][c];
+                    if piece == PieceType::Blank {
+                        empty += 1;
+                        continue;
+                    }
+                    if empty > 0 {
+                        rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    rank.push(piece_letter(piece));
+                }
+                if empty > 0 {
+                    rank.push_str(&empty.to_string());
+                }
+                rank
+            })
+            .collect::<Vec<String>>();
+        let side = if self.defenders_turn { 'd' } else { 'a' };
+        format!("{} {} {}", ranks.join("/"), side, self.variant())
+    }
+
+    /// Parses and validates a position. The game is over if the king is
+    /// on a corner or the side to move has no moves.
+    pub fn from_position_str(text: &str) -> Result<Game, PositionError> {
+        let mut fields = text.split_whitespace();
+        let (Some(ranks), Some(side), Some(variant), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(PositionError::MissingField);
+        };
+        let variant = variant
+            .parse::<Variant>()
+            .map_err(|_| PositionError::UnknownVariant(variant.to_string()))?;
+        let defenders_turn = match side {
+            "a" => false,
+            "d" => true,
+            _ => return Err(PositionError::BadSide(side.to_string())),
+        };
+
+        let size = variant.board_size();
+        let ranks = ranks.split('/').collect::<Vec<&str>>();
+        if ranks.len() != size {
+            return Err(PositionError::WrongRankCount {
+                expected: size,
+                found: ranks.len(),
+            });
+        }
+        let mut board = Board::empty();
+        for (c, rank) in ranks.iter().enumerate() {
+            let bad_rank = || PositionError::BadRank(rank.to_string());
+            let mut r = 0;
+            let mut empty = 0;
+            for letter in rank.chars() {
+                if let Some(digit) = letter.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    // stop before a long run of digits can overflow
+                    if r + empty > size {
+                        return Err(bad_rank());
+                    }
+                    continue;
+                }
+                r += empty;
+                empty = 0;
+                let piece = match letter {
+                    'a' => PieceType::Attacker,
+                    'd' => PieceType::Defender,
+                    'k' => PieceType::King,
+                    _ => return Err(bad_rank()),
+                };
+                if r >= size {
+                    return Err(bad_rank());
+                }
+                board.0[r][c] = piece;
+                r += 1;
+            }
+            if r + empty != size {
+                return Err(bad_rank());
+            }
+        }
+
+        let mut game = Game {
+            board,
+            defenders_turn,
+            game_over: false,
+            defender_won: false,
+        };
+        game.validate()?;
+
+        // positions that have already been decided
+        let king = game.king_tile().expect("validated to have a king");
+        if game.is_corner(king) {
+            game.game_over = true;
+            game.defender_won = true;
+        } else if game.get_all_valid_moves().next().is_none() {
+            game.game_over = true;
+            game.defender_won = !game.defenders_turn;
+        }
+        Ok(game)
+    }
+
+    fn validate(&self) -> Result<(), PositionError> {
+        let start = Game::new();
+        for piece in [PieceType::Attacker, PieceType::Defender, PieceType::King] {
+            let count = self.piece_tiles(piece).count();
+            if piece == PieceType::King && count != 1 {
+                return Err(PositionError::KingCount(count));
+            }
+            if count > start.piece_tiles(piece).count() {
+                return Err(PositionError::TooMany(piece));
+            }
+        }
+        let size = self.board_size();
+        let restricted = [(0, 0), (0, size - 1), (size - 1, 0), (size - 1, size - 1)]
+            .map(Tile::from)
+            .into_iter()
+            .chain([self.throne_tile()]);
+        for tile in restricted {
+            let piece = self.board.0[tile.r][tile.c];
+            if piece != PieceType::Blank && piece != PieceType::King {
+                return Err(PositionError::RestrictedTile(tile));
+            }
+        }
+        Ok(())
+    }
+
+    fn piece_tiles(&self, piece: PieceType) -> impl Iterator<Item = Tile> + '_ {
+        let size = self.board_size();
+        (0..size)
+            .flat_map(move |r| (0..size).map(move |c| Tile::from((r, c))))
+            .filter(move |tile| self.board.0[tile.r][tile.c] == piece)
+    }
+
+    pub fn king_tile(&self) -> Option<Tile> {
+        self.piece_tiles(PieceType::King).next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const START: &str = "3a3/3a3/3d3/aadkdaa/3d3/3a3/3a3 a brandubh";
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(Game::new().to_position_str(), START);
+        let game = Game::from_position_str(START).unwrap();
+        assert_eq!(game.board, Game::new().board);
+        assert!(!game.defenders_turn);
+
+        let game = Game::new().gen_next("d6".parse().unwrap(), "b6".parse().unwrap());
+        let text = game.to_position_str();
+        assert_eq!(text, "3a3/1a5/3d3/aadkdaa/3d3/3a3/3a3 d brandubh");
+        assert_eq!(Game::from_position_str(&text).unwrap().board, game.board);
+    }
+
+    #[test]
+    fn finished_positions() {
+        let game = Game::from_position_str("k6/7/7/7/7/7/5a1 d brandubh").unwrap();
+        assert!(game.game_over && game.defender_won);
+        // the attacker has no moves
+        let game = Game::from_position_str("7/7/7/2k4/6d/5da/7 a brandubh").unwrap();
+        assert!(game.game_over && game.defender_won);
+    }
+
+    #[test]
+    fn rejects_bad_positions() {
+        let error = |text| Game::from_position_str(text).unwrap_err();
+        assert_eq!(error("3a3/3a3 a"), PositionError::MissingField);
+        assert_eq!(
+            error("7/7/7/3k3/7/7/7 a tablut"),
+            PositionError::UnknownVariant("tablut".to_string())
+        );
+        assert_eq!(
+            error("7/7/7/3k3/7/7/7 x brandubh"),
+            PositionError::BadSide("x".to_string())
+        );
+        assert_eq!(
+            error("7/7/3k3/7/7/7 a brandubh"),
+            PositionError::WrongRankCount {
+                expected: 7,
+                found: 6
+            }
+        );
+        assert_eq!(
+            error("7/7/7/3k4/7/7/7 a brandubh"),
+            PositionError::BadRank("3k4".to_string())
+        );
+        assert_eq!(
+            error("7/7/7/99999999999999999999k/7/7/7 a brandubh"),
+            PositionError::BadRank("99999999999999999999k".to_string())
+        );
+        assert_eq!(
+            error("7/7/7/3q3/7/7/7 a brandubh"),
+            PositionError::BadRank("3q3".to_string())
+        );
+        assert_eq!(
+            error("7/7/7/7/7/7/7 a brandubh"),
+            PositionError::KingCount(0)
+        );
+        assert_eq!(
+            error("a6/7/7/3k3/7/7/7 a brandubh"),
+            PositionError::RestrictedTile("a7".parse().unwrap())
+        );
+        assert_eq!(
+            error("ddddd2/7/7/1k5/7/7/6a a brandubh"),
+            PositionError::TooMany(PieceType::Defender)
+        );
+    }
+}
@@ -0,0 +1,877 @@
+//! Complete game records, written as PGN-like text or in OpenTafl's format
+//!
+//! The PGN-like format has `[Key "value"]` tags followed by numbered moves:
+//!
+//! ```text
+//! [Variant "brandubh"]
+//! [Attacker "human"]
+//! [Defender "minimax:4"]
+//! [Result "*"]
+//!
+//! 1. d6-c6 e4-e6 2. c6-c5 e6-e7 3. d2-c2 e7-f7 4. c2-c3xc4 *
+//! ```
+//!
+//! A `Position` tag gives the start when it isn't the usual setup. Results are
+//! `attackers`, `defenders`, `draw` or `*` while the game is unfinished.
+//! Timed games save the `TimeControl` and each side's time left, like
+//! `[AttackerClock "25.5 x2"]` with the byoyomi periods once in overtime.
+
+use crate::clock::{Clock, SideClock, TimeControl};
+use crate::game::{Game, GameState, IllegalMove, Move, MoveReport, Outcome, Player, Tile, Variant};
+use crate::notation::NotationError;
+use crate::position::PositionError;
+use std::fmt::{self, Display};
+
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub variant: Variant,
+    pub attacker: String,
+    pub defender: String,
+    pub start: Game,
+    /// added with `play`, which keeps the end position up to date
+    moves: Vec<MoveReport>,
+    /// None while the game is unfinished
+    pub result: Option<Outcome>,
+    /// any other tags, like the event or date, in the order they were read
+    pub tags: Vec<(String, String)>,
+    /// position after the last move, so playing a move doesn't replay the game
+    end: Game,
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    /// a tag line that isn't `[Key "value"]`
+    Tag(String),
+    Position(PositionError),
+    Notation(NotationError),
+    IllegalMove(IllegalMove),
+    Result(String),
+    /// a move after the game had already ended
+    MoveAfterEnd(Move),
+    /// a time control or time left that can't be read
+    Clock(String),
+    /// the attacker or defender has no spec to save it as
+    Player(&'static str),
+    Io(std::io::Error),
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Tag(line) => write!(f, "bad tag {line}"),
+            RecordError::Position(error) => write!(f, "bad position: {error}"),
+            RecordError::Notation(error) => write!(f, "{error}"),
+            RecordError::IllegalMove(error) => write!(f, "{error}"),
+            RecordError::Result(result) => write!(f, "unknown result {result}"),
+            RecordError::MoveAfterEnd(mv) => write!(f, "move {mv} after the game ended"),
+            RecordError::Clock(clock) => write!(f, "bad clock {clock}"),
+            RecordError::Player(side) => write!(f, "the {side} can't be saved as a player spec"),
+            RecordError::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<PositionError> for RecordError {
+    fn from(error: PositionError) -> Self {
+        RecordError::Position(error)
+    }
+}
+
+impl From<NotationError> for RecordError {
+    fn from(error: NotationError) -> Self {
+        RecordError::Notation(error)
+    }
+}
+
+impl From<IllegalMove> for RecordError {
+    fn from(error: IllegalMove) -> Self {
+        RecordError::IllegalMove(error)
+    }
+}
+
+impl From<std::io::Error> for RecordError {
+    fn from(error: std::io::Error) -> Self {
+        RecordError::Io(error)
+    }
+}
+
+fn result_text(result: Option<Outcome>) -> &'static str {
+    match result {
+        Some(Outcome::AttackerWin) => "attackers",
+        Some(Outcome::DefenderWin) => "defenders",
+        Some(Outcome::Draw) => "draw",
+        None => "*",
+    }
+}
+
+fn parse_result(text: &str) -> Result<Option<Outcome>, RecordError> {
+    match text {
+        "attackers" => Ok(Some(Outcome::AttackerWin)),
+        "defenders" => Ok(Some(Outcome::DefenderWin)),
+        "draw" => Ok(Some(Outcome::Draw)),
+        "*" => Ok(None),
+        _ => Err(RecordError::Result(text.to_string())),
+    }
+}
+
+/// Tag values are quoted, with backslashes, quotes and line breaks escaped
+/// so each tag stays on one line
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::new();
+    for letter in value.chars() {
+        match letter {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// OpenTafl's tags aren't quoted, so a `]` would end them early
+fn escape_opentafl_tag(value: &str) -> String {
+    escape_tag(value).replace(']', "\\]")
+}
+
+/// the reverse of `escape_tag` and `escape_opentafl_tag`, None for an unknown escape
+fn unescape_tag(value: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut letters = value.chars();
+    while let Some(letter) = letters.next() {
+        if letter != '\\' {
+            unescaped.push(letter);
+            continue;
+        }
+        unescaped.push(match letters.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            escaped @ ('\\' | '"' | ']') => escaped,
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+/// seconds left, then the periods like ` x3` once in overtime
+fn side_clock_text(side: &SideClock) -> String {
+    let mut text = side.remaining.as_secs_f64().to_string();
+    if side.overtime {
+        text.push_str(&format!(" x{}", side.periods));
+    }
+    text
+}
+
+fn parse_side_clock(text: &str, control: &TimeControl) -> Option<SideClock> {
+    let (seconds, periods) = match text.split_once(" x") {
+        Some((seconds, periods)) => (seconds, Some(periods.parse().ok()?)),
+        None => (text, None),
+    };
+    let remaining = std::time::Duration::try_from_secs_f64(seconds.parse().ok()?).ok()?;
+    // periods are only used up in overtime
+    let full_periods = match *control {
+        TimeControl::Byoyomi { periods, .. } => periods,
+        _ => 0,
+    };
+    Some(SideClock {
+        remaining,
+        periods: periods.unwrap_or(full_periods),
+        overtime: periods.is_some(),
+    })
+}
+
+/// move numbers like `12.` or `12...`
+fn is_move_number(token: &str) -> bool {
+    let digits = token.trim_end_matches('.');
+    digits.len() < token.len() && !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl GameRecord {
+    pub fn new(start: Game, attacker: &str, defender: &str) -> Self {
+        GameRecord {
+            variant: start.variant(),
+            attacker: attacker.to_string(),
+            defender: defender.to_string(),
+            result: start.outcome(),
+            end: start.clone(),
+            start,
+            moves: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Replays `moves` from `start`, filling in the captures and result
+    pub fn from_moves(
+        start: Game,
+        attacker: &str,
+        defender: &str,
+        moves: &[Move],
+    ) -> Result<Self, RecordError> {
+        let mut record = GameRecord::new(start, attacker, defender);
+        for &mv in moves {
+            record.play(mv)?;
+        }
+        Ok(record)
+    }
+
+    /// Adds a move to the end of the record
+    pub fn play(&mut self, mv: Move) -> Result<&MoveReport, RecordError> {
+        if self.end.game_over {
+            return Err(RecordError::MoveAfterEnd(mv));
+        }
+        if !self.end.is_valid_move(mv) {
+            return Err(IllegalMove { mv }.into());
+        }
+        let (next, report) = self.end.apply_move(mv);
+        self.result = next.outcome();
+        self.end = next;
+        self.moves.push(report);
+        Ok(self.moves.last().unwrap())
+    }
+
+    /// every position from the start to the end of the game
+    pub fn positions(&self) -> Vec<Game> {
+        let mut positions = vec![self.start.clone()];
+        for report in &self.moves {
+            let next = positions.last().unwrap().apply_move(report.mv).0;
+            positions.push(next);
+        }
+        positions
+    }
+
+    pub fn moves(&self) -> &[MoveReport] {
+        &self.moves
+    }
+
+    /// position after the last move
+    pub fn end(&self) -> &Game {
+        &self.end
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// moves numbered in pairs, attackers' move first
+    fn numbered_moves(&self, notation: impl Fn(&MoveReport) -> String) -> String {
+        let mut tokens = Vec::new();
+        let mut defenders_turn = self.start.defenders_turn;
+        let mut number = 1;
+        if defenders_turn && !self.moves.is_empty() {
+            tokens.push(format!("{number}..."));
+        }
+        for report in &self.moves {
+            if !defenders_turn {
+                tokens.push(format!("{number}."));
+            } else {
+                number += 1;
+            }
+            tokens.push(notation(report));
+            defenders_turn = !defenders_turn;
+        }
+        tokens.join(" ")
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let size = self.variant.board_size();
+        let mut text = String::new();
+        let mut tags = vec![
+            ("Variant".to_string(), self.variant.to_string()),
+            ("Attacker".to_string(), self.attacker.clone()),
+            ("Defender".to_string(), self.defender.clone()),
+        ];
+        let position = self.start.to_position_str();
+        if position != Game::new().to_position_str() {
+            tags.push(("Position".to_string(), position));
+        }
+        tags.push(("Result".to_string(), result_text(self.result).to_string()));
+        for (key, value) in tags.iter().chain(&self.tags) {
+            text.push_str(&format!("[{key} \"{}\"]\n", escape_tag(value)));
+        }
+        text.push('\n');
+        let moves = self.numbered_moves(|report| report.to_notation(size));
+        if !moves.is_empty() {
+            text.push_str(&moves);
+            text.push(' ');
+        }
+        text.push_str(result_text(self.result));
+        text.push('\n');
+        text
+    }
+
+    pub fn from_pgn(text: &str) -> Result<Self, RecordError> {
+        let mut tags = Vec::new();
+        let mut movetext = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(tag) = line.strip_prefix('[') {
+                let bad_tag = || RecordError::Tag(line.to_string());
+                let (key, value) = tag
+                    .strip_suffix(']')
+                    .and_then(|tag| tag.split_once(' '))
+                    .ok_or_else(bad_tag)?;
+                let value = value
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .and_then(unescape_tag)
+                    .ok_or_else(bad_tag)?;
+                tags.push((key.to_string(), value));
+            } else {
+                movetext.extend(line.split_whitespace());
+            }
+        }
+
+        let mut take_tag = |key: &str| {
+            let index = tags.iter().position(|(name, _)| name == key)?;
+            Some(tags.remove(index).1)
+        };
+        let variant = match take_tag("Variant") {
+            Some(variant) => variant
+                .parse::<Variant>()
+                .map_err(|_| PositionError::UnknownVariant(variant))?,
+            None => Variant::Brandubh,
+        };
+        let start = match take_tag("Position") {
+            Some(position) => Game::from_position_str(&position)?,
+            None => Game::new(),
+        };
+        let attacker = take_tag("Attacker").unwrap_or_default();
+        let defender = take_tag("Defender").unwrap_or_default();
+        let tagged_result = take_tag("Result").map(|result| parse_result(&result));
+
+        let mut record = GameRecord::new(start, &attacker, &defender);
+        record.variant = variant;
+        record.tags = tags;
+        let size = variant.board_size();
+        let mut result = None;
+        for token in movetext {
+            if is_move_number(token) {
+                continue;
+            }
+            if let Ok(outcome) = parse_result(token) {
+                result = Some(outcome);
+                continue;
+            }
+            record.play(Move::from_notation(token, size)?)?;
+        }
+        // draws and resignations aren't decided by the moves
+        if let Some(result) = result.or(tagged_result.transpose()?) {
+            record.result = result.or(record.result);
+        }
+        Ok(record)
+    }
+
+    /// Record of a game in progress, players are saved as their specs and
+    /// undone moves in an `Undone` tag so they can still be redone.
+    /// Fails if a player can't be described by a spec.
+    pub fn from_state(state: &GameState) -> Result<Self, RecordError> {
+        let spec = |player: &dyn Player, side| player.spec().ok_or(RecordError::Player(side));
+        let mut record = GameRecord {
+            variant: state.game.variant(),
+            attacker: spec(state.attacker(), "attacker")?,
+            defender: spec(state.defender(), "defender")?,
+            start: state.start().clone(),
+            moves: state.history().to_vec(),
+            result: state.game.outcome(),
+            tags: Vec::new(),
+            end: state.game.clone(),
+        };
+        if state.can_redo() {
+            let size = record.variant.board_size();
+            let undone = state
+                .undone()
+                .iter()
+                .rev()
+                .map(|mv| mv.to_notation(size))
+                .collect::<Vec<String>>();
+            record.tags.push(("Undone".to_string(), undone.join(" ")));
+        }
+        if let Some(clock) = state.clock() {
+            record.tags.extend([
+                ("TimeControl".to_string(), clock.control.to_string()),
+                (
+                    "AttackerClock".to_string(),
+                    side_clock_text(&clock.attacker),
+                ),
+                (
+                    "DefenderClock".to_string(),
+                    side_clock_text(&clock.defender),
+                ),
+            ]);
+            // the clocks each move started with, so undoing can put them back
+            let past = state
+                .past_clocks()
+                .iter()
+                .map(|clock| match clock {
+                    Some(clock) => format!(
+                        "{}/{}",
+                        side_clock_text(&clock.attacker),
+                        side_clock_text(&clock.defender)
+                    ),
+                    None => "-".to_string(),
+                })
+                .collect::<Vec<String>>();
+            if !past.is_empty() {
+                record.tags.push(("Clocks".to_string(), past.join(";")));
+            }
+        }
+        if state.flag_fell() {
+            record
+                .tags
+                .push(("Termination".to_string(), "time forfeit".to_string()));
+        }
+        Ok(record)
+    }
+
+    /// Both sides' time from the `TimeControl`, `AttackerClock` and
+    /// `DefenderClock` tags, None for an untimed game
+    pub fn clock(&self) -> Result<Option<Clock>, RecordError> {
+        let Some(control) = self.tag("TimeControl") else {
+            return Ok(None);
+        };
+        let control = control
+            .parse::<TimeControl>()
+            .map_err(|_| RecordError::Clock(control.to_string()))?;
+        let mut clock = Clock::new(control);
+        for (key, side) in [
+            ("AttackerClock", &mut clock.attacker),
+            ("DefenderClock", &mut clock.defender),
+        ] {
+            // without the time left both sides have full clocks
+            if let Some(text) = self.tag(key) {
+                *side = parse_side_clock(text, &control)
+                    .ok_or_else(|| RecordError::Clock(text.to_string()))?;
+            }
+        }
+        Ok(Some(clock))
+    }
+
+    /// The clocks as each move was started from the `Clocks` tag, attacker then
+    /// defender like `10/10;8.5/10`, with `-` or nothing where they aren't known
+    pub fn past_clocks(&self) -> Result<Vec<Option<Clock>>, RecordError> {
+        let (Some(clock), Some(past)) = (self.clock()?, self.tag("Clocks")) else {
+            return Ok(Vec::new());
+        };
+        past.split(';')
+            .map(|text| {
+                if text == "-" {
+                    return Ok(None);
+                }
+                let bad_clock = || RecordError::Clock(text.to_string());
+                let (attacker, defender) = text.split_once('/').ok_or_else(bad_clock)?;
+                let side = |text| parse_side_clock(text, &clock.control).ok_or_else(bad_clock);
+                Ok(Some(Clock {
+                    attacker: side(attacker)?,
+                    defender: side(defender)?,
+                    ..clock
+                }))
+            })
+            .collect()
+    }
+
+    /// whether the side to move at the end lost on time
+    pub fn lost_on_time(&self) -> bool {
+        self.result.is_some() && self.tag("Termination") == Some("time forfeit")
+    }
+
+    /// moves in the `Undone` tag, next to redo first
+    pub fn undone(&self) -> Result<Vec<Move>, RecordError> {
+        let size = self.variant.board_size();
+        self.tag("Undone")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|mv| Ok(Move::from_notation(mv, size)?))
+            .collect()
+    }
+
+    /// writes the PGN-like format
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_pgn())
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, RecordError> {
+        GameRecord::from_pgn(&std::fs::read_to_string(path)?)
+    }
+
+    /// OpenTafl's record format, with `[key:value]` tags, the setup in the
+    /// rules tag and captures separated by `/` like `d5-c5xc4/b5`
+    pub fn to_opentafl(&self) -> String {
+        let size = self.variant.board_size();
+        let position = self.start.to_position_str();
+        let board = position.split_whitespace().next().unwrap();
+        let start = board
+            .chars()
+            .map(|letter| match letter {
+                'a' => 't',
+                'd' => 'T',
+                'k' => 'K',
+                other => other,
+            })
+            .collect::<String>();
+        let name = match self.variant {
+            Variant::Brandubh => "Brandubh",
+        };
+        let attackers_first = if self.start.defenders_turn { 'n' } else { 'y' };
+
+        let mut text = format!(
+            "[rules:dim:{size} name:{name} atkf:{attackers_first} start:/{start}/]\n\
+            [attackers:{}]\n[defenders:{}]\n[result:{}]\n",
+            escape_opentafl_tag(&self.attacker),
+            escape_opentafl_tag(&self.defender),
+            result_text(self.result)
+        );
+        for (key, value) in &self.tags {
+            text.push_str(&format!(
+                "[{}:{}]\n",
+                key.to_lowercase(),
+                escape_opentafl_tag(value)
+            ));
+        }
+        let moves = self.numbered_moves(|report| {
+            let mut text = report.mv.to_notation(size);
+            let captures = report
+                .captures
+                .iter()
+                .map(|tile| tile.to_notation(size))
+                .collect::<Vec<String>>();
+            if !captures.is_empty() {
+                text.push('x');
+                text.push_str(&captures.join("/"));
+            }
+            text
+        });
+        text.push_str(&moves);
+        text.push('\n');
+        text
+    }
+
+    pub fn from_opentafl(text: &str) -> Result<Self, RecordError> {
+        let mut tags = Vec::new();
+        let mut movetext = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(tag) = line.strip_prefix('[') {
+                let (key, value) = tag
+                    .strip_suffix(']')
+                    .and_then(|tag| tag.split_once(':'))
+                    .and_then(|(key, value)| Some((key, unescape_tag(value)?)))
+                    .ok_or_else(|| RecordError::Tag(line.to_string()))?;
+                tags.push((key.to_string(), value));
+            } else {
+                movetext.extend(line.split_whitespace());
+            }
+        }
+
+        let mut take_tag = |key: &str| {
+            let index = tags.iter().position(|(name, _)| name == key)?;
+            Some(tags.remove(index).1)
+        };
+        let start = match take_tag("rules") {
+            Some(rules) => {
+                let rule = |name: &str| {
+                    rules
+                        .split_whitespace()
+                        .find_map(|rule| rule.strip_prefix(name)?.strip_prefix(':'))
+                };
+                let variant = match rule("name") {
+                    Some("Brandubh") | None => Variant::Brandubh,
+                    Some(name) => return Err(PositionError::UnknownVariant(name.into()).into()),
+                };
+                let side = if rule("atkf") == Some("n") { 'd' } else { 'a' };
+                match rule("start") {
+                    Some(start) => {
+                        let board = start
+                            .trim_matches('/')
+                            .chars()
+                            .map(|letter| match letter {
+                                't' => 'a',
+                                'T' => 'd',
+                                'K' => 'k',
+                                // so unknown pieces are rejected
+                                'a' | 'd' | 'k' => '?',
+                                other => other,
+                            })
+                            .collect::<String>();
+                        Game::from_position_str(&format!("{board} {side} {variant}"))?
+                    }
+                    None => Game::new(),
+                }
+            }
+            None => Game::new(),
+        };
+        let attacker = take_tag("attackers").unwrap_or_default();
+        let defender = take_tag("defenders").unwrap_or_default();
+        let result = take_tag("result").map(|result| parse_result(&result));
+
+        let mut record = GameRecord::new(start, &attacker, &defender);
+        record.tags = tags;
+        let size = record.variant.board_size();
+        for token in movetext {
+            if is_move_number(token) {
+                continue;
+            }
+            // captures are decided by the rules, but still need to be tiles
+            let (mv, captures) = token.split_once('x').unwrap_or((token, ""));
+            for capture in captures.split('/').filter(|capture| !capture.is_empty()) {
+                Tile::from_notation(capture, size)?;
+            }
+            record.play(Move::from_notation(mv, size)?)?;
+        }
+        if let Some(result) = result.transpose()?.flatten() {
+            record.result = Some(result);
+        }
+        Ok(record)
+    }
+}
+
+impl GameState {
+    /// Resumes a saved game with the given players, see `GameRecord::from_state`
+    pub fn from_record(
+        record: &GameRecord,
+        defender: Box<dyn Player>,
+        attacker: Box<dyn Player>,
+    ) -> Result<Self, RecordError> {
+        let moves = record
+            .moves
+            .iter()
+            .map(|report| report.mv)
+            .collect::<Vec<Move>>();
+        let undone = record.undone()?;
+        let mut state =
+            GameState::from_history(record.start.clone(), &moves, &undone, defender, attacker)?;
+        if let Some(clock) = record.clock()? {
+            state.set_clock(clock, crate::clock::now);
+            state.set_past_clocks(&record.past_clocks()?);
+        }
+        if record.lost_on_time() {
+            state.flag_fall();
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headless::{block_on, ScriptedPlayer};
+
+    fn moves(notation: &[&str]) -> Vec<Move> {
+        notation.iter().map(|mv| mv.parse().unwrap()).collect()
+    }
+
+    /// a short game with a capture
+    fn sample() -> GameRecord {
+        let moves = moves(&[
+            "d6-c6", "e4-e6", "c6-c5", "e6-e7", "d2-c2", "e7-f7", "c2-c3",
+        ]);
+        let mut record = GameRecord::from_moves(Game::new(), "greedy", "human", &moves).unwrap();
+        record
+            .tags
+            .push(("Event".to_string(), "test \"match\"".to_string()));
+        record
+    }
+
+    fn assert_same(a: &GameRecord, b: &GameRecord) {
+        assert_eq!(a.variant, b.variant);
+        assert_eq!(a.attacker, b.attacker);
+        assert_eq!(a.defender, b.defender);
+        assert_eq!(a.start.to_position_str(), b.start.to_position_str());
+        assert_eq!(a.moves, b.moves);
+        assert_eq!(a.result, b.result);
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let mut record = sample();
+        // escapes in tags, which stay on one line
+        record
+            .tags
+            .push(("Annotator".to_string(), "a\\b\tc\nd é".to_string()));
+        let text = record.to_pgn();
+        assert!(text.contains("4. c2-c3xc4 *"), "{text}");
+        let read = GameRecord::from_pgn(&text).unwrap();
+        assert_same(&record, &read);
+        assert_eq!(read.tag("Event"), Some("test \"match\""));
+        assert_eq!(read.tag("Annotator"), Some("a\\b\tc\nd é"));
+        assert_eq!(read.to_pgn(), text);
+
+        // a finished game from a custom position, defenders to move
+        let start = Game::from_position_str("1a1k3/7/7/7/3a3/7/7 d brandubh").unwrap();
+        let record = GameRecord::from_moves(start, "a", "b", &moves(&["d7-g7"])).unwrap();
+        assert_eq!(record.result, Some(Outcome::DefenderWin));
+        let text = record.to_pgn();
+        assert!(text.contains("1... d7-g7 defenders"), "{text}");
+        assert_same(&record, &GameRecord::from_pgn(&text).unwrap());
+    }
+
+    #[test]
+    fn opentafl_round_trip() {
+        let mut record = sample();
+        // a `]` or line break in a name can't end the tag early
+        record.defender = "mcts:8:nets/[best]\nv2".to_string();
+        let text = record.to_opentafl();
+        assert!(text.starts_with(
+            "[rules:dim:7 name:Brandubh atkf:y start:/3t3/3t3/3T3/ttTKTtt/3T3/3t3/3t3/]"
+        ));
+        let read = GameRecord::from_opentafl(&text).unwrap();
+        assert_same(&record, &read);
+        assert_eq!(read.tag("event"), Some("test \"match\""));
+        assert_eq!(read.to_opentafl(), text);
+    }
+
+    /// scripted moves, saved as another player's spec
+    struct Described(ScriptedPlayer, &'static str);
+
+    impl Player for Described {
+        fn choose_move<'a>(&'a mut self, game: &'a Game) -> crate::game::MoveFuture<'a> {
+            self.0.choose_move(game)
+        }
+        fn spec(&self) -> Option<String> {
+            Some(self.1.to_string())
+        }
+    }
+
+    #[test]
+    fn saved_games_resume() {
+        use crate::ai::AIPlayer;
+
+        let defender = Box::new(Described(
+            ScriptedPlayer::new(moves(&["c4-c6", "e4-e5"])),
+            "greedy",
+        ));
+        let attacker = Box::new(Described(
+            ScriptedPlayer::new(moves(&["a4-a6", "b4-b5", "g4-g6"])),
+            "human",
+        ));
+        let mut state = GameState::new(defender, attacker);
+        for _ in 0..5 {
+            block_on(state.next_turn()).unwrap();
+        }
+        state.undo();
+        state.undo();
+
+        let path = std::env::temp_dir().join(format!("tafl-save-{}.pgn", std::process::id()));
+        GameRecord::from_state(&state).unwrap().save(&path).unwrap();
+        let record = GameRecord::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(record.defender, "greedy");
+        assert_eq!(record.attacker, "human");
+
+        let defender = Box::new(AIPlayer::from_spec(&record.defender).unwrap());
+        let attacker = Box::new(ScriptedPlayer::new([]));
+        let mut resumed = GameState::from_record(&record, defender, attacker).unwrap();
+        assert_eq!(resumed.game.board, state.game.board);
+        assert_eq!(resumed.history(), state.history());
+        assert_eq!(resumed.undone(), state.undone());
+        resumed.redo_turn();
+        state.redo_turn();
+        assert_eq!(resumed.game.board, state.game.board);
+
+        // a player with no spec isn't saved as an empty one
+        let state = GameState::new(
+            Box::new(ScriptedPlayer::new([])),
+            Box::new(ScriptedPlayer::new([])),
+        );
+        assert!(matches!(
+            GameRecord::from_state(&state),
+            Err(RecordError::Player("attacker"))
+        ));
+    }
+
+    #[test]
+    fn timed_games_resume() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use std::time::Duration;
+
+        let players = || {
+            (
+                Box::new(Described(ScriptedPlayer::new(moves(&["e4-e6"])), "human")),
+                Box::new(Described(ScriptedPlayer::new(moves(&["d6-c6"])), "human")),
+            )
+        };
+        let (defender, attacker) = players();
+        let mut state = GameState::new(defender, attacker);
+        let now = Rc::new(Cell::new(0.0));
+        let time = Rc::clone(&now);
+        state.set_clock(Clock::new("10:5x2".parse().unwrap()), move || time.get());
+        // into byoyomi for the first move, then partway through the defenders' main time
+        now.set(12.0);
+        block_on(state.next_turn()).unwrap();
+        now.set(13.5);
+
+        let text = GameRecord::from_state(&state).unwrap().to_pgn();
+        assert!(text.contains("[TimeControl \"10:5x2\"]"), "{text}");
+        assert!(text.contains("[AttackerClock \"5 x2\"]"), "{text}");
+        assert!(text.contains("[DefenderClock \"8.5\"]"), "{text}");
+        let record = GameRecord::from_pgn(&text).unwrap();
+        assert_eq!(record.clock().unwrap(), state.clock());
+        let (defender, attacker) = players();
+        let resumed = GameState::from_record(&record, defender, attacker).unwrap();
+        let clock = resumed.clock().unwrap();
+        assert_eq!(clock.attacker, state.clock().unwrap().attacker);
+        assert!(clock.defender.remaining <= Duration::from_secs_f64(8.5));
+        assert!(clock.defender.remaining > Duration::from_secs(8));
+        // undoing goes back to the clocks the first move started with
+        let mut resumed = resumed;
+        resumed.undo();
+        let attacker = resumed.clock().unwrap().attacker;
+        assert!(!attacker.overtime && attacker.remaining > Duration::from_secs(9));
+
+        // the defenders run out of time
+        now.set(40.0);
+        assert_eq!(block_on(state.next_turn()).unwrap(), None);
+        let text = GameRecord::from_state(&state).unwrap().to_pgn();
+        assert!(text.contains("[Termination \"time forfeit\"]"), "{text}");
+        let record = GameRecord::from_pgn(&text).unwrap();
+        assert!(record.lost_on_time());
+        let (defender, attacker) = players();
+        let resumed = GameState::from_record(&record, defender, attacker).unwrap();
+        assert!(resumed.flag_fell());
+        assert!(resumed.game.game_over && !resumed.game.defender_won);
+        assert!(resumed.clock().unwrap().defender.flagged());
+
+        assert!(matches!(
+            GameRecord::from_pgn("[TimeControl \"10\"]\n[AttackerClock \"5 x\"]")
+                .unwrap()
+                .clock(),
+            Err(RecordError::Clock(_))
+        ));
+    }
+
+    #[test]
+    fn results_and_errors() {
+        // a draw isn't decided by the moves
+        let record = GameRecord::from_pgn("1. d6-c6 e4-e6 draw").unwrap();
+        assert_eq!(record.moves.len(), 2);
+        assert_eq!(record.result, Some(Outcome::Draw));
+        let record = GameRecord::from_pgn("[Result \"draw\"]\n1. d6-c6").unwrap();
+        assert_eq!(record.result, Some(Outcome::Draw));
+
+        assert!(matches!(
+            GameRecord::from_pgn("1. d6-c6 d6-d5"),
+            Err(RecordError::IllegalMove(_))
+        ));
+        assert!(matches!(
+            GameRecord::from_pgn("1. d6-c9"),
+            Err(RecordError::Notation(_))
+        ));
+        assert!(matches!(
+            GameRecord::from_pgn("[Result draw]"),
+            Err(RecordError::Tag(_))
+        ));
+        assert!(matches!(
+            GameRecord::from_pgn("[Position \"7/7 a brandubh\"]"),
+            Err(RecordError::Position(_))
+        ));
+        assert!(matches!(
+            GameRecord::from_opentafl("[rules:dim:7 start:/3t3/]"),
+            Err(RecordError::Position(_))
+        ));
+    }
+}
@@ -0,0 +1,290 @@
+//! Players on the other end of a TCP connection, such as another copy of tafl
+//!
+//! Both ends send their own moves as lines of notation like `d6-b6`.
+//! One side listens with `remote:PORT`, or `remote:listen:HOST:PORT` to listen on
+//! other interfaces, and the other connects with `remote:HOST:PORT`, each playing
+//! the other as its remote player. As each end keeps its own copy of the game,
+//! moves can't be undone while a remote player is in it.
+
+use crate::game::{Game, Move, MoveFuture, Player};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::task::{Context, Poll};
+
+/// longest line accepted from the other player, far more than any move needs
+const MAX_LINE: usize = 256;
+
+enum Connection {
+    /// connecting on another thread so the window keeps drawing meanwhile
+    Connecting(Receiver<std::io::Result<TcpStream>>),
+    /// waiting for the other player, checked without blocking when a move is wanted
+    Listening(TcpListener),
+    Connected(TcpStream),
+}
+
+pub struct RemotePlayer {
+    connection: Connection,
+    /// received text not yet ending in a newline
+    buffer: Vec<u8>,
+    /// moves waiting to be sent until the other player connects
+    outgoing: Vec<u8>,
+    spec: String,
+    /// why the connection stopped, no more moves are chosen after this
+    error: Option<String>,
+}
+
+impl RemotePlayer {
+    fn new(connection: Connection, spec: &str) -> Self {
+        RemotePlayer {
+            connection,
+            buffer: Vec::new(),
+            outgoing: Vec::new(),
+            spec: spec.to_string(),
+            error: None,
+        }
+    }
+
+    /// waits for a connection on `listener` without blocking
+    fn accepting(listener: TcpListener, spec: &str) -> std::io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(RemotePlayer::new(Connection::Listening(listener), spec))
+    }
+
+    /// Connects to a listening player without waiting, `addr` is like
+    /// `localhost:7700`. Failing to connect shows up once a move is wanted.
+    pub fn connect(addr: &str) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let target = addr.to_string();
+        std::thread::Builder::new().spawn(move || {
+            // nobody is left to tell if the player was dropped meanwhile
+            let _ = sender.send(TcpStream::connect(target));
+        })?;
+        let spec = format!("remote:{addr}");
+        Ok(RemotePlayer::new(Connection::Connecting(receiver), &spec))
+    }
+
+    /// Listens on `host` and `port` without waiting, the other player is
+    /// accepted once it connects while moves are being asked for
+    pub fn listen(host: &str, port: u16) -> std::io::Result<Self> {
+        let spec = match host {
+            "127.0.0.1" => format!("remote:{port}"),
+            _ => format!("remote:listen:{host}:{port}"),
+        };
+        RemotePlayer::accepting(TcpListener::bind((host, port))?, &spec)
+    }
+
+    /// `remote:PORT` to listen on this machine, `remote:listen:HOST:PORT` to listen
+    /// on a given interface or `remote:HOST:PORT` to connect
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let addr = spec
+            .strip_prefix("remote:")
+            .ok_or_else(|| format!("not a remote player: {spec}"))?;
+        let bad_port = || format!("bad port in {spec}");
+        let player = if let Some(listen) = addr.strip_prefix("listen:") {
+            let (host, port) = listen.rsplit_once(':').ok_or_else(bad_port)?;
+            RemotePlayer::listen(host, port.parse().map_err(|_| bad_port())?)
+        } else {
+            match addr.parse::<u16>() {
+                Ok(port) => RemotePlayer::listen("127.0.0.1", port),
+                Err(_) => RemotePlayer::connect(addr),
+            }
+        };
+        player.map_err(|error| format!("{spec}: {error}"))
+    }
+
+    /// Keeps the error, after which the player only reports it
+    fn fail(&mut self, error: impl std::fmt::Display) -> Poll<Option<Move>> {
+        self.error = Some(format!("{}: {error}", self.spec));
+        Poll::Ready(None)
+    }
+
+    /// sends whatever is waiting once there is someone to send it to
+    fn flush(&mut self) {
+        let Connection::Connected(stream) = &mut self.connection else {
+            return;
+        };
+        if self.outgoing.is_empty() {
+            return;
+        }
+        if let Err(error) = stream.write_all(&self.outgoing) {
+            self.error = Some(format!("{}: {error}", self.spec));
+        }
+        self.outgoing.clear();
+    }
+
+    /// starts playing over `stream`, sending anything queued meanwhile
+    fn start(&mut self, stream: TcpStream) -> std::io::Result<()> {
+        // some platforms pass on the listener's non-blocking mode
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        self.connection = Connection::Connected(stream);
+        self.flush();
+        Ok(())
+    }
+
+    /// Accepts the other player or finishes connecting to it, if that has happened yet
+    fn poll_connection(&mut self, context: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let stream = match &self.connection {
+            Connection::Connected(_) => return Poll::Ready(Ok(())),
+            Connection::Connecting(receiver) => match receiver.try_recv() {
+                Ok(stream) => stream,
+                Err(TryRecvError::Empty) => Err(ErrorKind::WouldBlock.into()),
+                Err(TryRecvError::Disconnected) => Err(std::io::Error::other("could not connect")),
+            },
+            Connection::Listening(listener) => listener.accept().map(|(stream, _)| stream),
+        };
+        match stream {
+            Ok(stream) => Poll::Ready(self.start(stream)),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                context.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(error) => Poll::Ready(Err(error)),
+        }
+    }
+
+    /// Reads without blocking until a whole line has arrived.
+    /// Ready with None once the connection closes or sends something other
+    /// than a move, with the reason kept in `error`.
+    fn poll_move(&mut self, context: &mut Context<'_>, size: usize) -> Poll<Option<Move>> {
+        if self.error.is_some() {
+            return Poll::Ready(None);
+        }
+        match self.poll_connection(context) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(error)) => return self.fail(error),
+            Poll::Ready(Ok(())) => (),
+        }
+        let Connection::Connected(stream) = &mut self.connection else {
+            return Poll::Pending;
+        };
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+                let line = self.buffer.drain(..=end).collect::<Vec<u8>>();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return match Move::from_notation(&line, size) {
+                    Ok(mv) => Poll::Ready(Some(mv)),
+                    Err(error) => self.fail(error),
+                };
+            }
+
+            let mut bytes = [0; 64];
+            let read = stream
+                .set_nonblocking(true)
+                .and_then(|_| stream.read(&mut bytes));
+            let _ = stream.set_nonblocking(false);
+            match read {
+                Ok(0) => return self.fail("connection closed"),
+                Ok(count) => {
+                    self.buffer.extend_from_slice(&bytes[..count]);
+                    // a peer that never ends its line can't fill up memory
+                    if !self.buffer.contains(&b'\n') && self.buffer.len() > MAX_LINE {
+                        return self.fail("line too long");
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                    context.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return self.fail(error),
+            }
+        }
+    }
+}
+
+impl Player for RemotePlayer {
+    fn choose_move<'a>(&'a mut self, game: &'a Game) -> MoveFuture<'a> {
+        let size = game.board_size();
+        Box::pin(std::future::poll_fn(move |context| {
+            self.poll_move(context, size)
+        }))
+    }
+
+    fn opponent_moved(&mut self, game: &Game, mv: Move) {
+        let line = format!("{}\n", mv.to_notation(game.board_size()));
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.flush();
+    }
+
+    fn allows_undo(&self) -> bool {
+        false
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    fn spec(&self) -> Option<String> {
+        Some(self.spec.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Outcome;
+    use crate::headless::{play_game, ScriptedPlayer};
+
+    #[test]
+    fn games_over_tcp() {
+        let moves = |notation: &[&str]| {
+            notation
+                .iter()
+                .map(|mv| mv.parse().unwrap())
+                .collect::<Vec<Move>>()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        // the listening end plays the attackers, connecting plays defenders
+        let attackers = std::thread::spawn(move || {
+            // the attackers move before anyone has connected
+            let defender = Box::new(RemotePlayer::accepting(listener, "remote:0").unwrap());
+            let attacker = Box::new(ScriptedPlayer::new(moves(&["a4-a6", "b4-b5"])));
+            play_game(Game::new(), defender, attacker, 4).unwrap()
+        });
+        let defender = Box::new(ScriptedPlayer::new(moves(&["c4-c6", "e4-e5"])));
+        let attacker = Box::new(RemotePlayer::connect(&addr).unwrap());
+        let played = play_game(Game::new(), defender, attacker, 4).unwrap();
+        let other_end = attackers.join().unwrap();
+
+        assert_eq!(played.moves.len(), 4);
+        assert_eq!(played.moves, other_end.moves);
+        assert_eq!(played.outcome, Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn listening_does_not_block() {
+        let player = RemotePlayer::from_spec("remote:0").unwrap();
+        assert_eq!(player.spec(), Some("remote:0".to_string()));
+        assert!(RemotePlayer::from_spec("remote:listen:7700").is_err());
+        assert!(!player.allows_undo());
+    }
+
+    #[test]
+    fn long_lines_are_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let sender = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[b'a'; 2 * MAX_LINE]).unwrap();
+            stream
+        });
+        let defender = Box::new(RemotePlayer::connect(&addr).unwrap());
+        let attacker = Box::new(ScriptedPlayer::new([]));
+        let mut state = crate::game::GameState::from_game(
+            Game::from_position_str("3a3/3a3/3d3/aadkdaa/3d3/3a3/3a3 d brandubh").unwrap(),
+            defender,
+            attacker,
+        );
+        assert_eq!(crate::headless::block_on(state.next_turn()).unwrap(), None);
+        let error = state.defender().error().unwrap();
+        assert!(error.contains("line too long"), "{error}");
+        sender.join().unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// Small xorshift random number generator
+/// interior mutability so players can draw numbers from `&self`
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: Cell<u64>,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 so nearby seeds give unrelated streams, state must not be zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng {
+            state: Cell::new(z.max(1)),
+        }
+    }
+
+    /// Seeded from the std hasher keys, which are random per process
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().hash_one(0u64))
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let mut x = self.state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state.set(x);
+        x
+    }
+
+    /// uniform in 0..n, n must be positive
+    pub fn below(&self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// uniform in -amplitude..=amplitude
+    pub fn noise(&self, amplitude: i32) -> i32 {
+        if amplitude <= 0 {
+            return 0;
+        }
+        self.below(2 * amplitude as usize + 1) as i32 - amplitude
+    }
+
+    /// uniform in 0.0..1.0
+    pub fn next_f32(&self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}